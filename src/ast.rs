@@ -33,95 +33,157 @@ impl Eval for ValueNode {
 }
 
 
-pub struct BinaryOpNode {
-    pub first: Box<Eval>,
-    pub rest: Vec<(String, Box<Eval>)>,
+/// How many operands an `Operator` can be applied to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Unary,
+    Binary,
+    /// Operators like `+`/`-` that work in both a unary and binary position.
+    Either,
 }
 
-impl Eval for BinaryOpNode {
-    fn eval(&self, context: &Context) -> EvalResult {
-        let mut result = try!(self.first.eval(&context));
-        for &(ref op, ref arg) in &self.rest {
-            let arg = try!(arg.eval(&context));
-            match &op[..] {
-                "+" => result = try!(BinaryOpNode::eval_plus(&context, &result, &arg)),
-                "-" => result = try!(BinaryOpNode::eval_minus(&context, &result, &arg)),
-                // TODO(xion): other operators
-                _ => { return eval::Error::err(&format!("unknown operator: {}", op)); }
-            }
-        }
-        Ok(result)
-    }
+/// All the operators that can appear inside a `UnaryOpNode`
+/// or a `BinaryOpNode`.
+///
+/// Carrying the operator as a typed enum -- rather than the `String`
+/// it used to be -- means the `eval` matches on it are exhaustive:
+/// forgetting to handle a newly added operator is a compile error,
+/// not a runtime "unknown operator" string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Not,
+    And,
+    Or,
+    Plus,
+    Minus,
+    Times,
+    By,
+    Modulo,
+    Power,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    At,
+    BitNot,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 }
 
-impl BinaryOpNode {
-    /// Evaluate the "+" operator for two values.
-    fn eval_plus(context: &Context, left: &Value, right: &Value) -> EvalResult {
-        if let &Value::String(ref left) = left {
-            if let &Value::String(ref right) = right {
-                return Ok(Value::String(left.clone() + &*right));
-            }
-        }
-        if let Value::Integer(left) = *left {
-            if let Value::Integer(right) = *right {
-                return Ok(Value::Integer(left + right));
-            }
+impl Operator {
+    /// The textual symbol the parser recognizes for this operator.
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            Operator::Not => "!",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Times => "*",
+            Operator::By => "/",
+            Operator::Modulo => "%",
+            Operator::Power => "**",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+            Operator::At => "@",
+            Operator::BitNot => "~",
+            Operator::BitAnd => "&",
+            Operator::BitOr => "|",
+            Operator::BitXor => "^",
+            Operator::Shl => "<<",
+            Operator::Shr => ">>",
         }
-        if let Value::Float(left) = *left {
-            if let Value::Float(right) = *right {
-                return Ok(Value::Float(left + right));
-            }
+    }
+
+    /// Whether the operator can be used as unary, binary, or both.
+    pub fn arity(&self) -> Arity {
+        match *self {
+            Operator::Not | Operator::BitNot => Arity::Unary,
+            Operator::Plus | Operator::Minus => Arity::Either,
+            _ => Arity::Binary,
         }
-        eval::Error::err("invalid types for (+) operator")
     }
 
-    /// Evaluate the "-" operator for two values.
-    fn eval_minus(context: &Context, left: &Value, right: &Value) -> EvalResult {
-        if let Value::Integer(left) = *left {
-            if let Value::Integer(right) = *right {
-                return Ok(Value::Integer(left - right));
-            }
+    /// Binding strength used by the parser to decide how operators
+    /// of different kinds group together. Higher binds tighter.
+    pub fn precedence(&self) -> u8 {
+        match *self {
+            Operator::Or => 0,
+            Operator::And => 1,
+            Operator::Eq | Operator::Ne |
+            Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge |
+            Operator::At => 2,
+            Operator::BitOr => 3,
+            Operator::BitXor => 3,
+            Operator::BitAnd => 3,
+            Operator::Shl | Operator::Shr => 3,
+            Operator::Plus | Operator::Minus => 4,
+            Operator::Times | Operator::By | Operator::Modulo => 5,
+            Operator::Power => 6,
+            Operator::Not | Operator::BitNot => 7,
         }
-        if let Value::Float(left) = *left {
-            if let Value::Float(right) = *right {
-                return Ok(Value::Float(left - right));
-            }
+    }
+}
+
+impl FromStr for Operator {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Operator, ()> {
+        match s {
+            "!" => Ok(Operator::Not),
+            "&&" => Ok(Operator::And),
+            "||" => Ok(Operator::Or),
+            "+" => Ok(Operator::Plus),
+            "-" => Ok(Operator::Minus),
+            "*" => Ok(Operator::Times),
+            "/" => Ok(Operator::By),
+            "%" => Ok(Operator::Modulo),
+            "**" => Ok(Operator::Power),
+            "<" => Ok(Operator::Lt),
+            "<=" => Ok(Operator::Le),
+            ">" => Ok(Operator::Gt),
+            ">=" => Ok(Operator::Ge),
+            "==" => Ok(Operator::Eq),
+            "!=" => Ok(Operator::Ne),
+            "@" => Ok(Operator::At),
+            "~" => Ok(Operator::BitNot),
+            "&" => Ok(Operator::BitAnd),
+            "|" => Ok(Operator::BitOr),
+            "^" => Ok(Operator::BitXor),
+            "<<" => Ok(Operator::Shl),
+            ">>" => Ok(Operator::Shr),
+            _ => Err(()),
         }
-        eval::Error::err("invalid types for (-) operator")
     }
 }
 
 
-// // TODO(xion): change to general OperatorNode that has starting value
-// // and arbitrary number of (op, value) pairs that it goes over during
-// // evaluation (the parser shall take care of operator precedence while
-// // building the tree)
-// pub struct BinaryOpNode {
-//     pub op: String,  // TODO(xion): enum?
-//     pub left: Box<Eval>,
-//     pub right: Box<Eval>,
-// }
-
-// impl Eval for BinaryOpNode {
-//     fn eval(&self, context: &Context) -> Result<Value, eval::Error> {
-//         match &self.op[..] {
-//             "+" => {
-//                 let left = try!(self.left.eval(&context));
-//                 let right = try!(self.right.eval(&context));
-
-//                 if let Value::String(left) = left {
-//                     if let Value::String(right) = right {
-//                         return Ok(Value::String(left + &right));
-//                     }
-//                 }
-//                 // TODO(xion): adding numbers
-//                 eval::Error::err("invalid types for + operator")
-//             }
-//             // TODO(xion): other operators
-//             _ => eval::Error::err(&format!("unknown operator: {}", self.op))
-//         }
-//     }
-// }
+pub struct UnaryOpNode {
+    pub op: Operator,
+    pub arg: Box<Eval>,
+}
+
+
+pub struct BinaryOpNode {
+    pub first: Box<Eval>,
+    pub rest: Vec<(Operator, Box<Eval>)>,
+}
+
+
+pub struct ConditionalNode {
+    pub cond: Box<Eval>,
+    pub then: Box<Eval>,
+    pub else_: Box<Eval>,
+}
 
 
 pub struct FunctionCallNode {
@@ -129,19 +191,33 @@ pub struct FunctionCallNode {
     pub args: Vec<Box<Eval>>,
 }
 
-impl Eval for FunctionCallNode {
-    fn eval(&self, context: &Context) -> Result<Value, eval::Error> {
-        // evaluate all the arguments first, bail if any of that fails
-        let evals: Vec<_> =
-            self.args.iter().map(|x| x.eval(&context)).collect();
-        if let Some(res) = evals.iter().find(|r| r.is_err()) {
-            return res.clone();
-        }
 
-        // extract the argument values and call the function
-        let args = evals.iter().map(|r| r.clone().ok().unwrap()).collect();
-        context.call_func(&self.name, args).ok_or(
-            eval::Error{message: format!("unknown function: {}", self.name)}
-        )
-    }
+/// A `start:end` or `start:end:step` slice spec used as an `IndexNode`'s
+/// index, e.g. the `1:3` in `_[1:3]`. Each part is optional, so `_[:2]`
+/// and `_[-2:]` are also valid; evaluates to a `Value::Range`.
+pub struct RangeNode {
+    pub start: Option<Box<Eval>>,
+    pub end: Option<Box<Eval>>,
+    pub step: Option<Box<Eval>>,
+}
+
+
+/// The `subject | name(args...)` pipeline operator.
+///
+/// `subject` is evaluated and prepended to `args` as the first argument
+/// of the call to `name`; `subject | name` (a bare function name, no
+/// parentheses) is just the case where `args` is empty.
+pub struct PipelineNode {
+    pub subject: Box<Eval>,
+    pub name: String,
+    pub args: Vec<Box<Eval>>,
+}
+
+
+/// The `subject[index]` postfix operator, for pulling a single element
+/// out of an `Array`/`Object`/`Map`/`String` value, or (with a `RangeNode`
+/// index) a slice out of an `Array`/`String`.
+pub struct IndexNode {
+    pub subject: Box<Eval>,
+    pub index: Box<Eval>,
 }
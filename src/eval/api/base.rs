@@ -1,18 +1,35 @@
 //! Base API functions.
+//!
+//! Beyond the eager `map`/`filter`, this also has a fuller combinator
+//! library (`zip`, `take`/`drop`, `take_while`/`drop_while`, `flatten`/
+//! `flatmap`, `chunks`/`windows`, `distinct`, `groupby`, `partition`,
+//! `scan`) for expressing real data pipelines over arrays without
+//! resorting to manual folds.
+
+use std::collections::HashMap;
+
+use unicode_segmentation::UnicodeSegmentation;
 
 use eval::{self, Context, Error, Function, Value};
 use eval::model::Invoke;
 use eval::value::IntegerRepr;
-use super::conv::bool;
+use eval::api::{bool, str_};
 
 
-/// Compute the length of given value (an array or a string).
+/// Compute the length of given value (an array, a string, an object,
+/// or a map).
+///
+/// For strings, this counts grapheme clusters (user-perceived characters)
+/// rather than bytes or codepoints, so `len("noël")` is `4`.
 pub fn len(value: Value) -> eval::Result {
-    eval1!((value: &String) -> Integer { value.len() as IntegerRepr });
+    eval1!((value: &String) -> Integer {
+        UnicodeSegmentation::graphemes(value.as_str(), true).count() as IntegerRepr
+    });
     eval1!((value: &Array) -> Integer { value.len() as IntegerRepr });
     eval1!((value: &Object) -> Integer { value.len() as IntegerRepr });
+    eval1!((value: &Map) -> Integer { value.len() as IntegerRepr });
     Err(Error::new(&format!(
-        "len() requires string/array/object, got {}", value.typename()
+        "len() requires string/array/object/map, got {}", value.typename()
     )))
 }
 
@@ -147,6 +164,316 @@ pub fn filter(func: Value, array: Value, context: &Context) -> eval::Result {
 }
 
 
+/// Like `reduce()`, but returns an array of every intermediate
+/// accumulator value instead of just the final one (the first element
+/// of the result is always `init`).
+pub fn scan(func: Value, init: Value, array: Value, context: &Context) -> eval::Result {
+    let array_type = array.typename();
+
+    eval3!((func: &Function, init: Value, array: Array) -> Array {{
+        try!(ensure_binary(&func, "scan"));
+
+        let mut acc = init;
+        let mut result = Vec::with_capacity(array.len() + 1);
+        result.push(acc.clone());
+        for item in array.into_iter() {
+            let context = Context::with_parent(&context);
+            acc = try!(func.invoke(vec![acc, item], &context));
+            result.push(acc.clone());
+        }
+        result
+    }});
+
+    Err(Error::new(&format!(
+        "scan() requires a function, an initial value and an array, got {} and {}",
+        func.typename(), array_type
+    )))
+}
+
+
+/// Pair up the elements of two arrays, stopping as soon as either one
+/// runs out; `zip([1,2,3], ["a","b"])` is `[[1,"a"], [2,"b"]]`.
+pub fn zip(left: Value, right: Value) -> eval::Result {
+    if let (Value::Array(l), Value::Array(r)) = (left.clone(), right.clone()) {
+        return Ok(Value::Array(
+            l.into_iter().zip(r.into_iter())
+                .map(|(a, b)| Value::Array(vec![a, b]))
+                .collect()
+        ));
+    }
+    Err(Error::new(&format!(
+        "zip() requires two arrays, got {} and {}",
+        left.typename(), right.typename()
+    )))
+}
+
+/// Pair up each element of an array with its index, as `[index, value]`.
+pub fn enumerate(array: Value) -> eval::Result {
+    let array_type = array.typename();
+
+    eval1!((array: Array) -> Array {
+        array.into_iter().enumerate()
+            .map(|(i, v)| Value::Array(vec![Value::Integer(i as IntegerRepr), v]))
+            .collect()
+    });
+
+    Err(Error::new(&format!(
+        "enumerate() requires an array, got {}", array_type
+    )))
+}
+
+/// Take the first `n` elements of an array (or all of them, if it's shorter).
+pub fn take(count: Value, array: Value) -> eval::Result {
+    let array_type = array.typename();
+
+    eval2!((count: Integer, array: Array) -> Array {{
+        let count = if count < 0 { 0 } else { count as usize };
+        array.into_iter().take(count).collect()
+    }});
+
+    Err(Error::new(&format!(
+        "take() requires an integer and an array, got {} and {}",
+        count.typename(), array_type
+    )))
+}
+
+/// Drop the first `n` elements of an array (or all of them, if it's shorter).
+pub fn drop(count: Value, array: Value) -> eval::Result {
+    let array_type = array.typename();
+
+    eval2!((count: Integer, array: Array) -> Array {{
+        let count = if count < 0 { 0 } else { count as usize };
+        array.into_iter().skip(count).collect()
+    }});
+
+    Err(Error::new(&format!(
+        "drop() requires an integer and an array, got {} and {}",
+        count.typename(), array_type
+    )))
+}
+
+/// Take elements off the front of an array for as long as a predicate
+/// function holds true, stopping at the first element it rejects.
+pub fn take_while(func: Value, array: Value, context: &Context) -> eval::Result {
+    let array_type = array.typename();
+
+    eval2!((func: &Function, array: Array) -> Array {{
+        try!(ensure_unary(&func, "take_while"));
+
+        let mut result = Vec::new();
+        for item in array.into_iter() {
+            let context = Context::with_parent(&context);
+            let keep = try!(
+                func.invoke(vec![item.clone()], &context).and_then(bool)
+            ).unwrap_bool();
+            if !keep {
+                break;
+            }
+            result.push(item);
+        }
+        result
+    }});
+
+    Err(Error::new(&format!(
+        "take_while() requires a function and an array, got {} and {}",
+        func.typename(), array_type
+    )))
+}
+
+/// Drop elements off the front of an array for as long as a predicate
+/// function holds true, keeping the first element it rejects and
+/// everything after it.
+pub fn drop_while(func: Value, array: Value, context: &Context) -> eval::Result {
+    let array_type = array.typename();
+
+    eval2!((func: &Function, array: Array) -> Array {{
+        try!(ensure_unary(&func, "drop_while"));
+
+        let mut iter = array.into_iter();
+        let mut dropping = true;
+        let mut result = Vec::new();
+        for item in iter.by_ref() {
+            if dropping {
+                let context = Context::with_parent(&context);
+                let keep = try!(
+                    func.invoke(vec![item.clone()], &context).and_then(bool)
+                ).unwrap_bool();
+                if keep {
+                    continue;
+                }
+                dropping = false;
+            }
+            result.push(item);
+        }
+        result
+    }});
+
+    Err(Error::new(&format!(
+        "drop_while() requires a function and an array, got {} and {}",
+        func.typename(), array_type
+    )))
+}
+
+/// Flatten one level of nesting out of an array of arrays.
+pub fn flatten(array: Value) -> eval::Result {
+    let array_type = array.typename();
+
+    eval1!((array: Array) -> Array {{
+        let mut result = Vec::new();
+        for item in array.into_iter() {
+            match item {
+                Value::Array(inner) => result.extend(inner.into_iter()),
+                other => result.push(other),
+            }
+        }
+        result
+    }});
+
+    Err(Error::new(&format!(
+        "flatten() requires an array, got {}", array_type
+    )))
+}
+
+/// Map a function over an array, then flatten one level of nesting out
+/// of the result; equivalent to `flatten(map(func, array))`.
+pub fn flatmap(func: Value, array: Value, context: &Context) -> eval::Result {
+    let mapped = try!(map(func, array, &context));
+    flatten(mapped)
+}
+
+/// Split an array into consecutive, non-overlapping chunks of `n`
+/// elements; the final chunk may be shorter if the array's length
+/// isn't an exact multiple of `n`.
+pub fn chunks(count: Value, array: Value) -> eval::Result {
+    let array_type = array.typename();
+
+    eval2!((count: Integer, array: Array) -> Array {{
+        if count <= 0 {
+            return Err(Error::new("chunks() requires a positive chunk size"));
+        }
+        let count = count as usize;
+        array.chunks(count).map(|c| Value::Array(c.to_vec())).collect()
+    }});
+
+    Err(Error::new(&format!(
+        "chunks() requires an integer and an array, got {} and {}",
+        count.typename(), array_type
+    )))
+}
+
+/// Slide a window of `n` elements over an array, one step at a time;
+/// yields nothing if the array is shorter than `n`.
+pub fn windows(count: Value, array: Value) -> eval::Result {
+    let array_type = array.typename();
+
+    eval2!((count: Integer, array: Array) -> Array {{
+        if count <= 0 {
+            return Err(Error::new("windows() requires a positive window size"));
+        }
+        let count = count as usize;
+        if array.len() < count {
+            Vec::new()
+        } else {
+            array.windows(count).map(|w| Value::Array(w.to_vec())).collect()
+        }
+    }});
+
+    Err(Error::new(&format!(
+        "windows() requires an integer and an array, got {} and {}",
+        count.typename(), array_type
+    )))
+}
+
+/// Remove duplicate elements from an array, preserving the order of
+/// their first occurrence.
+pub fn distinct(array: Value) -> eval::Result {
+    let array_type = array.typename();
+
+    eval1!((array: Array) -> Array {{
+        let mut seen = Vec::with_capacity(array.len());
+        for item in array.into_iter() {
+            if !seen.contains(&item) {
+                seen.push(item);
+            }
+        }
+        seen
+    }});
+
+    Err(Error::new(&format!(
+        "distinct() requires an array, got {}", array_type
+    )))
+}
+
+/// Group the elements of an array by the (stringified) result of a key
+/// function, returning a map from each key to the array of elements that
+/// produced it.
+///
+/// Returns a `Map` (not an `Object`) so the result feeds straight into
+/// `keys()`/`values()`/`items()` -- e.g. `groupby(f, array) | keys`.
+pub fn groupby(func: Value, array: Value, context: &Context) -> eval::Result {
+    let array_type = array.typename();
+
+    eval2!((func: &Function, array: Array) -> Map {{
+        try!(ensure_unary(&func, "groupby"));
+
+        let mut order = Vec::new();
+        let mut groups: HashMap<String, Vec<Value>> = HashMap::new();
+        for item in array.into_iter() {
+            let context = Context::with_parent(&context);
+            let key = try!(
+                func.invoke(vec![item.clone()], &context).and_then(str_)
+            ).unwrap_string();
+            if !groups.contains_key(&key) {
+                order.push(key.clone());
+            }
+            groups.entry(key).or_insert_with(Vec::new).push(item);
+        }
+
+        order.into_iter()
+            .map(|key| {
+                let values = groups.remove(&key).unwrap();
+                (key, Value::Array(values))
+            })
+            .collect()
+    }});
+
+    Err(Error::new(&format!(
+        "groupby() requires a function and an array, got {} and {}",
+        func.typename(), array_type
+    )))
+}
+
+/// Split an array into two, by whether each element makes a predicate
+/// function return true; result is `[matching, non_matching]`.
+pub fn partition(func: Value, array: Value, context: &Context) -> eval::Result {
+    let array_type = array.typename();
+
+    eval2!((func: &Function, array: Array) -> Array {{
+        try!(ensure_unary(&func, "partition"));
+
+        let mut matching = Vec::new();
+        let mut non_matching = Vec::new();
+        for item in array.into_iter() {
+            let context = Context::with_parent(&context);
+            let keep = try!(
+                func.invoke(vec![item.clone()], &context).and_then(bool)
+            ).unwrap_bool();
+            if keep {
+                matching.push(item);
+            } else {
+                non_matching.push(item);
+            }
+        }
+        vec![Value::Array(matching), Value::Array(non_matching)]
+    }});
+
+    Err(Error::new(&format!(
+        "partition() requires a function and an array, got {} and {}",
+        func.typename(), array_type
+    )))
+}
+
+
 // Utility functions
 
 #[inline(always)]
@@ -160,3 +487,15 @@ fn ensure_unary(func: &Function, api_call: &str) -> Result<(), Error> {
     }
     Ok(())
 }
+
+#[inline(always)]
+fn ensure_binary(func: &Function, api_call: &str) -> Result<(), Error> {
+    let arity = func.arity();
+    if !arity.accepts(2) {
+        return Err(Error::new(&format!(
+            "{}() requires a 2-argument function, got one with {} arguments",
+            api_call, arity
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,633 @@
+//! Mathematical API functions: the basics (`abs`, `round`, ...), the
+//! trigonometric/logarithmic family, a handful of number-theoretic
+//! helpers (`gcd`, `lcm`, `factorial`), and the `Context`-backed RNG.
+
+use rand::Rng;
+
+use eval::{self, Context, Error, Value};
+use eval::model::Args;
+use eval::value::IntegerRepr;
+
+
+// Basics
+
+/// Compute the absolute value of a number.
+///
+/// Uses checked arithmetic so `abs(i64::MIN)` errors out cleanly instead
+/// of silently wrapping back around to a negative number.
+pub fn abs(value: Value) -> eval::Result {
+    eval1!(value : Integer {{
+        try!(value.checked_abs().ok_or_else(|| Error::IntegerOverflow{
+            operation: "abs()".to_string(),
+        }))
+    }});
+    eval1!(value : Float { value.abs() });
+    if let Value::Complex(re, im) = value {
+        return Ok(Value::Float(re.hypot(im)));
+    }
+    Err(Error::new(&format!(
+        "abs() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the signum function.
+pub fn sgn(value: Value) -> eval::Result {
+    eval1!(value : Integer {
+        match value {
+            v if v < 0 => -1,
+            v if v > 0 => 1,
+            _ => 0,
+        }
+    });
+    eval1!(value : Float {
+        match value {
+            v if v < 0.0 => -1.0,
+            v if v > 0.0 => 1.0,
+            _ => 0.0,
+        }
+    });
+    Err(Error::new(&format!(
+        "sgn() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Round a number up to the nearest integer.
+pub fn ceil(value: Value) -> eval::Result {
+    eval1!(value : Integer { value });
+    eval1!(value : Float { value.ceil() });
+    Err(Error::new(&format!(
+        "ceil() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Round a number down to the nearest integer.
+pub fn floor(value: Value) -> eval::Result {
+    eval1!(value : Integer { value });
+    eval1!(value : Float { value.floor() });
+    Err(Error::new(&format!(
+        "floor() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Round a number to the nearest integer.
+pub fn round(value: Value) -> eval::Result {
+    eval1!(value : Integer { value });
+    eval1!(value : Float { value.round() });
+    Err(Error::new(&format!(
+        "round() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Truncate a number's fractional part.
+pub fn trunc(value: Value) -> eval::Result {
+    eval1!(value : Integer { value });
+    eval1!(value : Float { value.trunc() });
+    Err(Error::new(&format!(
+        "trunc() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the square root of a number.
+///
+/// The square root of a negative real yields a `Complex` result instead
+/// of erroring, the way it would in a language without complex numbers.
+pub fn sqrt(value: Value) -> eval::Result {
+    if let Value::Integer(i) = value {
+        let f = i as f64;
+        return Ok(if f >= 0.0 {
+            Value::Float(f.sqrt())
+        } else {
+            Value::Complex(0.0, (-f).sqrt())
+        });
+    }
+    if let Value::Float(f) = value {
+        return Ok(if f >= 0.0 {
+            Value::Float(f.sqrt())
+        } else {
+            Value::Complex(0.0, (-f).sqrt())
+        });
+    }
+    if let Value::Complex(re, im) = value {
+        let (r, theta) = (re.hypot(im), im.atan2(re));
+        let sqrt_r = r.sqrt();
+        return Ok(Value::Complex(sqrt_r * (theta / 2.0).cos(), sqrt_r * (theta / 2.0).sin()));
+    }
+    Err(Error::new(&format!(
+        "sqrt() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Raise e to the power of a number.
+pub fn exp(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).exp() });
+    eval1!((value: Float) -> Float { value.exp() });
+    if let Value::Complex(re, im) = value {
+        let (r, i) = complex_exp(re, im);
+        return Ok(Value::Complex(r, i));
+    }
+    Err(Error::new(&format!(
+        "exp() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the natural logarithm of a number.
+/// Complex inputs (and negative reals, via a `Complex` promotion) yield
+/// a `Complex` result.
+pub fn ln(value: Value) -> eval::Result {
+    if let Value::Integer(i) = value {
+        let f = i as f64;
+        return Ok(if f > 0.0 {
+            Value::Float(f.ln())
+        } else {
+            let (r, im) = complex_ln(f, 0.0);
+            Value::Complex(r, im)
+        });
+    }
+    if let Value::Float(f) = value {
+        return Ok(if f > 0.0 {
+            Value::Float(f.ln())
+        } else {
+            let (r, im) = complex_ln(f, 0.0);
+            Value::Complex(r, im)
+        });
+    }
+    if let Value::Complex(re, im) = value {
+        let (r, i) = complex_ln(re, im);
+        return Ok(Value::Complex(r, i));
+    }
+    Err(Error::new(&format!(
+        "ln() requires a number, got {}", value.typename()
+    )))
+}
+
+
+// Binary-to-text/radix conversions
+
+/// Format an integer as a `0b`-prefixed binary string.
+pub fn bin(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> String { format!("0b{:b}", value) });
+    Err(Error::new(&format!(
+        "bin() requires an integer, got {}", value.typename()
+    )))
+}
+
+/// Format an integer as a `0x`-prefixed hexadecimal string.
+pub fn hex(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> String { format!("0x{:x}", value) });
+    Err(Error::new(&format!(
+        "hex() requires an integer, got {}", value.typename()
+    )))
+}
+
+/// Format an integer as a `0o`-prefixed octal string.
+pub fn oct(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> String { format!("0o{:o}", value) });
+    Err(Error::new(&format!(
+        "oct() requires an integer, got {}", value.typename()
+    )))
+}
+
+
+// Random numbers
+//
+// The generator lives in the root Context (child contexts, e.g. the ones
+// `map`/`filter` spin up per element, share it via `Context::rng`), so
+// seeding it once at the start of a run makes every draw reproducible.
+
+/// Fix the RNG's state, so subsequent `rand`/`randint`/`choice`/`shuffle`/
+/// `sample` calls in this run become reproducible.
+pub fn seed(value: Value, context: &Context) -> eval::Result {
+    if let Value::Integer(n) = value {
+        context.seed_rng(n as u64);
+        return Ok(Value::Empty);
+    }
+    Err(Error::new(&format!(
+        "seed() requires an integer, got {}", value.typename()
+    )))
+}
+
+/// Generate a random floating point number: `rand()` draws from `[0, 1)`,
+/// `rand(hi)` from `[0, hi)`, and `rand(lo, hi)` from `[lo, hi)`.
+pub fn rand(args: Args, context: &Context) -> eval::Result {
+    let mut args = args.into_iter();
+    match (args.next(), args.next(), args.next()) {
+        (None, None, None) => Ok(Value::Float(context.rng(|rng| rng.gen::<f64>()))),
+        (Some(hi), None, None) => {
+            let hi = try!(as_float_arg(&hi, "rand"));
+            Ok(Value::Float(context.rng(|rng| rng.gen_range(0.0, hi))))
+        },
+        (Some(lo), Some(hi), None) => {
+            let lo = try!(as_float_arg(&lo, "rand"));
+            let hi = try!(as_float_arg(&hi, "rand"));
+            Ok(Value::Float(context.rng(|rng| rng.gen_range(lo, hi))))
+        },
+        _ => Err(Error::new("rand() takes 0, 1 or 2 arguments")),
+    }
+}
+
+/// Draw a random integer from the inclusive range `[lo, hi]`.
+pub fn randint(lo: Value, hi: Value, context: &Context) -> eval::Result {
+    let (lo_type, hi_type) = (lo.typename(), hi.typename());
+    if let (Value::Integer(lo), Value::Integer(hi)) = (lo, hi) {
+        if lo > hi {
+            return Err(Error::new("randint() requires lo <= hi"));
+        }
+        return Ok(Value::Integer(context.rng(|rng| rng.gen_range(lo, hi + 1))));
+    }
+    Err(Error::new(&format!(
+        "randint() requires two integers, got {} and {}", lo_type, hi_type
+    )))
+}
+
+/// Pick a uniformly random element of an array.
+pub fn choice(array: Value, context: &Context) -> eval::Result {
+    let array_type = array.typename();
+    if let Value::Array(items) = array {
+        if items.is_empty() {
+            return Err(Error::new("choice() requires a non-empty array"));
+        }
+        let idx = context.rng(|rng| rng.gen_range(0, items.len()));
+        return Ok(items[idx].clone());
+    }
+    Err(Error::new(&format!(
+        "choice() requires an array, got {}", array_type
+    )))
+}
+
+/// Return a randomly-shuffled copy of an array (Fisher-Yates).
+pub fn shuffle(array: Value, context: &Context) -> eval::Result {
+    let array_type = array.typename();
+    if let Value::Array(mut items) = array {
+        context.rng(|rng| rng.shuffle(&mut items));
+        return Ok(Value::Array(items));
+    }
+    Err(Error::new(&format!(
+        "shuffle() requires an array, got {}", array_type
+    )))
+}
+
+/// Pick `count` distinct elements of an array, in random order.
+/// If `count` exceeds the array's length, the whole (shuffled) array
+/// is returned.
+pub fn sample(array: Value, count: Value, context: &Context) -> eval::Result {
+    let array_type = array.typename();
+    let count_type = count.typename();
+    if let (Value::Array(mut items), Value::Integer(k)) = (array, count) {
+        if k < 0 {
+            return Err(Error::new("sample() requires a non-negative count"));
+        }
+        let k = (k as usize).min(items.len());
+        context.rng(|rng| rng.shuffle(&mut items));
+        items.truncate(k);
+        return Ok(Value::Array(items));
+    }
+    Err(Error::new(&format!(
+        "sample() requires an array and an integer, got {} and {}",
+        array_type, count_type
+    )))
+}
+
+fn as_float_arg(value: &Value, func: &str) -> Result<f64, Error> {
+    as_float(value).ok_or_else(|| Error::new(&format!(
+        "{}() requires a number, got {}", func, value.typename()
+    )))
+}
+
+
+// Trigonometry
+
+/// Compute the sine of an angle, in radians.
+pub fn sin(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).sin() });
+    eval1!((value: Float) -> Float { value.sin() });
+    Err(Error::new(&format!(
+        "sin() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the cosine of an angle, in radians.
+pub fn cos(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).cos() });
+    eval1!((value: Float) -> Float { value.cos() });
+    Err(Error::new(&format!(
+        "cos() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the tangent of an angle, in radians.
+pub fn tan(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).tan() });
+    eval1!((value: Float) -> Float { value.tan() });
+    Err(Error::new(&format!(
+        "tan() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the arcsine of a number, in radians. Domain is `[-1, 1]`.
+pub fn asin(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float {{ try!(unit_range(value as f64, "asin")).asin() }});
+    eval1!((value: Float) -> Float {{ try!(unit_range(value, "asin")).asin() }});
+    Err(Error::new(&format!(
+        "asin() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the arccosine of a number, in radians. Domain is `[-1, 1]`.
+pub fn acos(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float {{ try!(unit_range(value as f64, "acos")).acos() }});
+    eval1!((value: Float) -> Float {{ try!(unit_range(value, "acos")).acos() }});
+    Err(Error::new(&format!(
+        "acos() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the arctangent of a number, in radians.
+pub fn atan(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).atan() });
+    eval1!((value: Float) -> Float { value.atan() });
+    Err(Error::new(&format!(
+        "atan() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the four-quadrant arctangent of `y / x`, in radians.
+pub fn atan2(y: Value, x: Value) -> eval::Result {
+    if let (Some(y), Some(x)) = (as_float(&y), as_float(&x)) {
+        return Ok(Value::Float(y.atan2(x)));
+    }
+    Err(Error::new(&format!(
+        "atan2() requires two numbers, got {} and {}", y.typename(), x.typename()
+    )))
+}
+
+/// Compute the hyperbolic sine of a number.
+pub fn sinh(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).sinh() });
+    eval1!((value: Float) -> Float { value.sinh() });
+    Err(Error::new(&format!(
+        "sinh() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the hyperbolic cosine of a number.
+pub fn cosh(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).cosh() });
+    eval1!((value: Float) -> Float { value.cosh() });
+    Err(Error::new(&format!(
+        "cosh() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the hyperbolic tangent of a number.
+pub fn tanh(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).tanh() });
+    eval1!((value: Float) -> Float { value.tanh() });
+    Err(Error::new(&format!(
+        "tanh() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Convert an angle from radians to degrees.
+pub fn deg(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).to_degrees() });
+    eval1!((value: Float) -> Float { value.to_degrees() });
+    Err(Error::new(&format!(
+        "deg() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Convert an angle from degrees to radians.
+pub fn rad(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float { (value as f64).to_radians() });
+    eval1!((value: Float) -> Float { value.to_radians() });
+    Err(Error::new(&format!(
+        "rad() requires a number, got {}", value.typename()
+    )))
+}
+
+
+// Logarithms, powers and roots
+
+/// Compute the logarithm of `value` in the given `base`.
+pub fn log(value: Value, base: Value) -> eval::Result {
+    if let (Some(value), Some(base)) = (as_float(&value), as_float(&base)) {
+        let value = try!(positive(value, "log"));
+        if base <= 0.0 || base == 1.0 {
+            return Err(Error::new("log() requires a base > 0 and != 1"));
+        }
+        return Ok(Value::Float(value.log(base)));
+    }
+    Err(Error::new(&format!(
+        "log() requires two numbers, got {} and {}", value.typename(), base.typename()
+    )))
+}
+
+/// Compute the base-2 logarithm of a number.
+pub fn log2(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float {{ try!(positive(value as f64, "log2")).log2() }});
+    eval1!((value: Float) -> Float {{ try!(positive(value, "log2")).log2() }});
+    Err(Error::new(&format!(
+        "log2() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Compute the base-10 logarithm of a number.
+pub fn log10(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Float {{ try!(positive(value as f64, "log10")).log10() }});
+    eval1!((value: Float) -> Float {{ try!(positive(value, "log10")).log10() }});
+    Err(Error::new(&format!(
+        "log10() requires a number, got {}", value.typename()
+    )))
+}
+
+/// Raise `base` to the `exponent` power.
+/// Stays an exact `Integer` for a non-negative integer exponent; falls
+/// back to `Complex` (via `z**w == exp(w * ln(z))`) if either side is
+/// complex, or if a negative real is raised to a fractional power.
+pub fn pow(base: Value, exponent: Value) -> eval::Result {
+    if let (&Value::Integer(b), &Value::Integer(e)) = (&base, &exponent) {
+        if e >= 0 {
+            return Ok(Value::Integer(b.pow(e as u32)));
+        }
+    }
+    if let (Some(b), Some(e)) = (as_float(&base), as_float(&exponent)) {
+        if b >= 0.0 || e.fract() == 0.0 {
+            return Ok(Value::Float(b.powf(e)));
+        }
+    }
+    if let (Some((br, bi)), Some((er, ei))) = (as_complex(&base), as_complex(&exponent)) {
+        let (lr, li) = complex_ln(br, bi);
+        let (re, im) = complex_mul((lr, li), (er, ei));
+        let (r, i) = complex_exp(re, im);
+        return Ok(Value::Complex(r, i));
+    }
+    Err(Error::new(&format!(
+        "pow() requires two numbers, got {} and {}", base.typename(), exponent.typename()
+    )))
+}
+
+/// Compute the length of the hypotenuse of a right triangle with legs
+/// `a` and `b`, i.e. `sqrt(a*a + b*b)` without the intermediate overflow
+/// or precision loss that naive formula can suffer.
+pub fn hypot(a: Value, b: Value) -> eval::Result {
+    if let (Some(a), Some(b)) = (as_float(&a), as_float(&b)) {
+        return Ok(Value::Float(a.hypot(b)));
+    }
+    Err(Error::new(&format!(
+        "hypot() requires two numbers, got {} and {}", a.typename(), b.typename()
+    )))
+}
+
+
+// Complex numbers
+
+/// Build a complex number out of its real and imaginary parts.
+pub fn complex(re: Value, im: Value) -> eval::Result {
+    if let (Some(re), Some(im)) = (as_float(&re), as_float(&im)) {
+        return Ok(Value::Complex(re, im));
+    }
+    Err(Error::new(&format!(
+        "complex() requires two numbers, got {} and {}", re.typename(), im.typename()
+    )))
+}
+
+/// Extract the real part of a number (a plain `Integer`/`Float` is its
+/// own real part).
+pub fn re(value: Value) -> eval::Result {
+    match as_complex(&value) {
+        Some((re, _)) => Ok(Value::Float(re)),
+        None => Err(Error::new(&format!(
+            "re() requires a number, got {}", value.typename()
+        ))),
+    }
+}
+
+/// Extract the imaginary part of a number (zero for a plain
+/// `Integer`/`Float`).
+pub fn im(value: Value) -> eval::Result {
+    match as_complex(&value) {
+        Some((_, im)) => Ok(Value::Float(im)),
+        None => Err(Error::new(&format!(
+            "im() requires a number, got {}", value.typename()
+        ))),
+    }
+}
+
+/// Compute the complex conjugate of a number (a plain `Integer`/`Float`
+/// is its own conjugate).
+pub fn conj(value: Value) -> eval::Result {
+    match as_complex(&value) {
+        Some((re, im)) => Ok(Value::Complex(re, -im)),
+        None => Err(Error::new(&format!(
+            "conj() requires a number, got {}", value.typename()
+        ))),
+    }
+}
+
+/// Compute the phase angle (argument) of a number, in radians.
+pub fn arg(value: Value) -> eval::Result {
+    match as_complex(&value) {
+        Some((re, im)) => Ok(Value::Float(im.atan2(re))),
+        None => Err(Error::new(&format!(
+            "arg() requires a number, got {}", value.typename()
+        ))),
+    }
+}
+
+fn as_complex(value: &Value) -> Option<(f64, f64)> {
+    match *value {
+        Value::Integer(i) => Some((i as f64, 0.0)),
+        Value::Float(f) => Some((f, 0.0)),
+        Value::Complex(re, im) => Some((re, im)),
+        _ => None,
+    }
+}
+
+fn complex_mul((ar, ai): (f64, f64), (br, bi): (f64, f64)) -> (f64, f64) {
+    (ar * br - ai * bi, ar * bi + ai * br)
+}
+
+fn complex_exp(re: f64, im: f64) -> (f64, f64) {
+    let scale = re.exp();
+    (scale * im.cos(), scale * im.sin())
+}
+
+fn complex_ln(re: f64, im: f64) -> (f64, f64) {
+    (re.hypot(im).ln(), im.atan2(re))
+}
+
+
+// Number theory
+
+/// Compute the greatest common divisor of two integers.
+pub fn gcd(a: Value, b: Value) -> eval::Result {
+    eval2!((a: Integer, b: Integer) -> Integer { gcd_(a.abs(), b.abs()) });
+    Err(Error::new(&format!(
+        "gcd() requires two integers, got {} and {}", a.typename(), b.typename()
+    )))
+}
+
+/// Compute the least common multiple of two integers.
+pub fn lcm(a: Value, b: Value) -> eval::Result {
+    eval2!((a: Integer, b: Integer) -> Integer {{
+        if a == 0 || b == 0 {
+            0
+        } else {
+            (a.abs() / gcd_(a.abs(), b.abs())) * b.abs()
+        }
+    }});
+    Err(Error::new(&format!(
+        "lcm() requires two integers, got {} and {}", a.typename(), b.typename()
+    )))
+}
+
+fn gcd_(a: IntegerRepr, b: IntegerRepr) -> IntegerRepr {
+    if b == 0 { a } else { gcd_(b, a % b) }
+}
+
+/// Compute the factorial of a non-negative integer.
+pub fn factorial(value: Value) -> eval::Result {
+    eval1!((value: Integer) -> Integer {{
+        if value < 0 {
+            return Err(Error::new("factorial() is not defined for negative numbers"));
+        }
+        let mut result: IntegerRepr = 1;
+        let mut i: IntegerRepr = 2;
+        while i <= value {
+            result = try!(result.checked_mul(i).ok_or_else(|| Error::IntegerOverflow{
+                operation: "factorial()".to_string(),
+            }));
+            i += 1;
+        }
+        result
+    }});
+    Err(Error::new(&format!(
+        "factorial() requires an integer, got {}", value.typename()
+    )))
+}
+
+
+// Shared helpers
+
+fn as_float(value: &Value) -> Option<f64> {
+    match *value {
+        Value::Integer(i) => Some(i as f64),
+        Value::Float(f) => Some(f),
+        _ => None,
+    }
+}
+
+fn positive(value: f64, func: &str) -> Result<f64, Error> {
+    if value <= 0.0 {
+        return Err(Error::new(&format!("{}() requires a positive argument", func)));
+    }
+    Ok(value)
+}
+
+fn unit_range(value: f64, func: &str) -> Result<f64, Error> {
+    if value < -1.0 || value > 1.0 {
+        return Err(Error::new(&format!("{}() requires an argument in [-1, 1]", func)));
+    }
+    Ok(value)
+}
+
@@ -0,0 +1,193 @@
+//! String-related API functions.
+
+use std::iter;
+
+use eval::{self, Error, Value};
+use eval::api::str_;
+
+
+/// Split a string by given string delimiter.
+/// Returns an array of strings.
+pub fn split(string: Value, delim: Value) -> eval::Result {
+    eval2!((string: &String, delim: &String) -> Array {
+        string.split(delim).map(str::to_owned).map(Value::String).collect()
+    });
+    Err(Error::new(&format!(
+        "split() expects two strings, got: {}, {}",
+        string.typename(), delim.typename()
+    )))
+}
+
+/// Join an array of values into a single delimited string.
+pub fn join(array: Value, delim: Value) -> eval::Result {
+    if let (&Value::Array(ref a),
+            &Value::String(ref d)) = (&array, &delim) {
+        let strings: Vec<_> = a.iter()
+            .map(|v| str_(v.clone())).filter(Result::is_ok)
+            .map(Result::unwrap).map(Value::unwrap_string)
+            .collect();
+        let error_count = strings.len() - a.len();
+        if error_count == 0 {
+            return Ok(Value::String(strings.join(&d)));
+        } else {
+            return Err(Error::new(&format!(
+                "join() failed to stringify {} element(s) of the input array",
+                error_count)));
+        }
+    }
+    Err(Error::new(&format!(
+        "join() expects an array and string, got: {}, {}",
+        array.typename(), delim.typename()
+    )))
+}
+
+/// Format a string using a printf-style format spec (the `%` operator),
+/// consuming arguments from `args` in order (`args` may be a single value
+/// or an array of them).
+///
+/// Supports `%%` for a literal `%`, the `d`/`i` (integer), `f` (float)
+/// and `s` (any value, via its string form) conversions, and the `-`
+/// (left-justify), `0` (zero-pad) and `+` (force sign) flags together
+/// with a minimum width and, for `%f`/`%s`, a `.precision`.
+pub fn format_(format: Value, args: Value) -> eval::Result {
+    let format = match format {
+        Value::String(s) => s,
+        _ => return Err(Error::new(&format!(
+            "format() requires a string, got {}", format.typename()
+        ))),
+    };
+    let args: Vec<Value> = match args {
+        Value::Array(a) => a,
+        other => vec![other],
+    };
+    let mut args = args.into_iter();
+
+    let mut result = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            result.push('%');
+            continue;
+        }
+
+        let mut left_justify = false;
+        let mut zero_pad = false;
+        let mut force_sign = false;
+        loop {
+            match chars.peek() {
+                Some(&'-') => { left_justify = true; chars.next(); },
+                Some(&'0') => { zero_pad = true; chars.next(); },
+                Some(&'+') => { force_sign = true; chars.next(); },
+                _ => break,
+            }
+        }
+
+        let width = read_digits(&mut chars).unwrap_or(0);
+
+        let precision = if chars.peek() == Some(&'.') {
+            chars.next();
+            Some(read_digits(&mut chars).unwrap_or(0))
+        } else {
+            None
+        };
+
+        let conversion = try!(chars.next().ok_or_else(|| Error::new(
+            "incomplete % placeholder at the end of the format string"
+        )));
+        let arg = try!(args.next().ok_or_else(|| Error::new(&format!(
+            "not enough arguments for the `%{}` placeholder", conversion
+        ))));
+
+        let formatted = try!(format_one(conversion, arg, force_sign, precision));
+        result.push_str(&pad(&formatted, width, left_justify, zero_pad));
+    }
+
+    Ok(Value::String(result))
+}
+
+/// Format a single value according to a single conversion character.
+fn format_one(conversion: char, arg: Value,
+               force_sign: bool, precision: Option<usize>) -> Result<String, Error> {
+    match conversion {
+        'd' | 'i' => match arg {
+            Value::Integer(i) => Ok(signed(i.to_string(), i >= 0, force_sign)),
+            _ => Err(Error::new(&format!(
+                "`%{}` requires an integer argument, got {}", conversion, arg.typename()
+            ))),
+        },
+        'f' => match arg {
+            Value::Float(f) => {
+                let s = match precision {
+                    Some(p) => format!("{:.*}", p, f),
+                    None => f.to_string(),
+                };
+                Ok(signed(s, f >= 0.0, force_sign))
+            },
+            _ => Err(Error::new(&format!(
+                "`%f` requires a float argument, got {}", arg.typename()
+            ))),
+        },
+        's' => {
+            let s = format!("{}", arg);
+            Ok(match precision {
+                // `String::truncate` cuts at a byte offset, which panics
+                // if `p` doesn't land on a UTF-8 character boundary (e.g.
+                // `"%.1s" % "é"`); truncate by char instead so a bad
+                // precision can only ever produce a shorter string, never
+                // crash the process.
+                Some(p) => s.chars().take(p).collect(),
+                None => s,
+            })
+        },
+        _ => Err(Error::new(&format!(
+            "unknown format directive `%{}`", conversion
+        ))),
+    }
+}
+
+/// Prepend a `+` to an already-non-negative, already-stringified number
+/// if the `+` flag was given.
+fn signed(s: String, non_negative: bool, force_sign: bool) -> String {
+    if force_sign && non_negative {
+        format!("+{}", s)
+    } else {
+        s
+    }
+}
+
+/// Pad a formatted directive out to its minimum field width.
+fn pad(s: &str, width: usize, left_justify: bool, zero_pad: bool) -> String {
+    if s.len() >= width {
+        return s.to_string();
+    }
+    let filler: String = iter::repeat(
+        if zero_pad && !left_justify { '0' } else { ' ' }
+    ).take(width - s.len()).collect();
+
+    if left_justify {
+        format!("{}{}", s, filler)
+    } else if zero_pad && (s.starts_with('-') || s.starts_with('+')) {
+        format!("{}{}{}", &s[..1], filler, &s[1..])
+    } else {
+        format!("{}{}", filler, s)
+    }
+}
+
+fn read_digits<I: Iterator<Item=char>>(chars: &mut iter::Peekable<I>) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(&d) = chars.peek() {
+        if d.is_digit(10) {
+            digits.push(d);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    if digits.is_empty() { None } else { digits.parse().ok() }
+}
@@ -0,0 +1,94 @@
+//! Structured error values produced while evaluating an expression.
+
+use std::fmt;
+
+use eval::value::IntegerRepr;
+
+
+/// An error that occurred while evaluating an expression.
+///
+/// This used to be a single struct wrapping a preformatted message string,
+/// which meant embedding code could only show the error to a human and
+/// never act on *what* actually went wrong. The variants below carry that
+/// detail instead, while `Display` still renders the same text the old
+/// ad hoc `format!()` calls produced, so CLI output is unchanged.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// An operator got operand type(s) it doesn't support.
+    ///
+    /// `actual` holds the `Debug` representation of each offending operand
+    /// (not its type name) so `Display` can reproduce the old ad hoc
+    /// `"invalid argument(s) for ... operator: ..."` wording verbatim;
+    /// `expected` is kept alongside for embedding code that wants a
+    /// machine-readable description of what would have worked instead.
+    WrongTypeCombination {
+        operator: String,
+        expected: String,
+        actual: Vec<String>,
+    },
+    /// A binary or unary operator the evaluator doesn't recognize.
+    UnknownOperator(String),
+    /// The `**` exponent (or a shift count) was out of the range
+    /// the evaluator can compute.
+    ExponentOutOfRange(IntegerRepr),
+    /// The condition of a ternary (`cond ? then : else`) expression
+    /// wasn't a boolean.
+    ExpectedBoolean { actual: String },
+    /// The subject of a `value[index]` expression doesn't support
+    /// indexing, or was indexed with a value of the wrong type.
+    NotIndexable { value_type: String },
+    /// An array or string index was outside the bounds of the collection.
+    IndexOutOfBounds { index: IntegerRepr, length: usize },
+    /// An object was indexed with a key it doesn't contain.
+    KeyNotFound(String),
+    /// A checked integer operation (see `--checked`) overflowed.
+    IntegerOverflow { operation: String },
+    /// Anything else; kept around so call sites that only have
+    /// a human-readable message don't need a dedicated variant.
+    Message(String),
+}
+
+impl Error {
+    /// Build a plain, free-form error out of a message string.
+    pub fn new(message: &str) -> Error {
+        Error::Message(message.to_string())
+    }
+
+    /// Convenience for call sites that want to return the error directly
+    /// as a `Result`, e.g. `return eval::Error::err("...")`.
+    pub fn err<T>(message: &str) -> Result<T, Error> {
+        Err(Error::new(message))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::WrongTypeCombination{ref operator, ref actual, ..} => match actual.len() {
+                1 => write!(fmt,
+                    "invalid argument for `{}` operator: `{}`", operator, actual[0]
+                ),
+                _ => write!(fmt,
+                    "invalid arguments for `{}` operator: `{}` and `{}`",
+                    operator, actual[0], actual[1]
+                ),
+            },
+            Error::UnknownOperator(ref op) => write!(fmt, "unknown operator: `{}`", op),
+            Error::ExponentOutOfRange(exp) => write!(fmt, "exponent out of range: {}", exp),
+            Error::ExpectedBoolean{ref actual} => write!(fmt,
+                "expected a boolean condition, got {} instead", actual
+            ),
+            Error::NotIndexable{ref value_type} => write!(fmt,
+                "{} is not indexable, or was given an index of the wrong type", value_type
+            ),
+            Error::IndexOutOfBounds{index, length} => write!(fmt,
+                "index {} out of bounds (length {})", index, length
+            ),
+            Error::KeyNotFound(ref key) => write!(fmt, "key not found: `{}`", key),
+            Error::IntegerOverflow{ref operation} => write!(fmt,
+                "integer overflow while computing `{}`", operation
+            ),
+            Error::Message(ref msg) => write!(fmt, "{}", msg),
+        }
+    }
+}
@@ -4,20 +4,25 @@ use std::iter;
 
 use eval::{self, api, Context, Eval, Value};
 use eval::model::value::{ArrayRepr, FloatRepr, IntegerRepr, StringRepr};
-use parse::ast::{BinaryOpNode, ConditionalNode, UnaryOpNode};
+use ast::{BinaryOpNode, ConditionalNode, Operator, UnaryOpNode};
 
 
 /// Evaluate the unary operator AST node.
 impl Eval for UnaryOpNode {
     fn eval(&self, context: &Context) -> eval::Result {
         let arg = try!(self.arg.eval(&context));
-        match &self.op[..] {
-            "+" => UnaryOpNode::eval_plus(arg),
-            "-" => UnaryOpNode::eval_minus(arg),
-            "!" => UnaryOpNode::eval_bang(arg),
-            _ => Err(eval::Error::new(
-                &format!("unknown unary operator: `{}`", self.op)
-            ))
+        match self.op {
+            Operator::Plus => UnaryOpNode::eval_plus(arg),
+            Operator::Minus => UnaryOpNode::eval_minus(arg),
+            Operator::Not => UnaryOpNode::eval_bang(arg),
+            Operator::BitNot => UnaryOpNode::eval_bitnot(arg),
+            Operator::And | Operator::Or | Operator::Times | Operator::By |
+            Operator::Modulo | Operator::Power | Operator::Lt | Operator::Le |
+            Operator::Gt | Operator::Ge | Operator::Eq | Operator::Ne |
+            Operator::At | Operator::BitAnd | Operator::BitOr | Operator::BitXor |
+            Operator::Shl | Operator::Shr => Err(eval::Error::new(&format!(
+                "`{}` cannot be used as a unary operator", self.op.symbol()
+            ))),
         }
     }
 }
@@ -43,11 +48,19 @@ impl UnaryOpNode {
         UnaryOpNode::err("!", &arg)
     }
 
+    /// Evaluate the "~" (bitwise complement) operator for one value.
+    fn eval_bitnot(arg: Value) -> eval::Result {
+        eval1!(arg : Integer { !arg });
+        UnaryOpNode::err("~", &arg)
+    }
+
     /// Produce an error about invalid argument for an operator.
     fn err(op: &str, arg: &Value) -> eval::Result {
-        Err(eval::Error::new(&format!(
-            "invalid argument for `{}` operator: `{:?}`", op, arg
-        )))
+        Err(eval::Error::WrongTypeCombination{
+            operator: op.to_string(),
+            expected: "a number or boolean".to_string(),
+            actual: vec![format!("{:?}", arg)],
+        })
     }
 }
 
@@ -56,31 +69,79 @@ impl UnaryOpNode {
 impl Eval for BinaryOpNode {
     fn eval(&self, context: &Context) -> eval::Result {
         let mut result = try!(self.first.eval(&context));
-        for &(ref op, ref arg) in &self.rest {
-            let arg = try!(arg.eval(&context));
-            match &op[..] {
-                "<" => result = try!(BinaryOpNode::eval_lt(result, arg)),
-                "<=" => result = try!(BinaryOpNode::eval_le(result, arg)),
-                ">" => result = try!(BinaryOpNode::eval_gt(result, arg)),
-                ">=" => result = try!(BinaryOpNode::eval_ge(result, arg)),
-                "==" => result = try!(BinaryOpNode::eval_eq(result, arg)),
-                "!=" => result = try!(BinaryOpNode::eval_ne(result, arg)),
-                "@" => result = try!(BinaryOpNode::eval_at(result, arg)),
-                "+" => result = try!(BinaryOpNode::eval_plus(result, arg)),
-                "-" => result = try!(BinaryOpNode::eval_minus(result, arg)),
-                "*" => result = try!(BinaryOpNode::eval_times(result, arg)),
-                "/" => result = try!(BinaryOpNode::eval_by(result, arg)),
-                "%" => result = try!(BinaryOpNode::eval_modulo(result, arg)),
-                "**" => result = try!(BinaryOpNode::eval_power(result, arg)),
-                _ => { return Err(
-                    eval::Error::new(&format!("unknown binary operator: `{}`", op))
-                ); }
-            }
+        for &(op, ref arg) in &self.rest {
+            // `&&`/`||` short-circuit, so unlike every other operator
+            // the right-hand side must not be evaluated eagerly here --
+            // only once `eval_and`/`eval_or` decide it's actually needed.
+            result = try!(match op {
+                Operator::And => BinaryOpNode::eval_and(&context, result, arg),
+                Operator::Or => BinaryOpNode::eval_or(&context, result, arg),
+                _ => {
+                    let arg = try!(arg.eval(&context));
+                    match op {
+                        Operator::Lt => BinaryOpNode::eval_lt(result, arg),
+                        Operator::Le => BinaryOpNode::eval_le(result, arg),
+                        Operator::Gt => BinaryOpNode::eval_gt(result, arg),
+                        Operator::Ge => BinaryOpNode::eval_ge(result, arg),
+                        Operator::Eq => BinaryOpNode::eval_eq(result, arg),
+                        Operator::Ne => BinaryOpNode::eval_ne(result, arg),
+                        Operator::At => BinaryOpNode::eval_at(result, arg),
+                        Operator::Plus => BinaryOpNode::eval_plus(&context, result, arg),
+                        Operator::Minus => BinaryOpNode::eval_minus(&context, result, arg),
+                        Operator::Times => BinaryOpNode::eval_times(&context, result, arg),
+                        Operator::By => BinaryOpNode::eval_by(result, arg),
+                        Operator::Modulo => BinaryOpNode::eval_modulo(result, arg),
+                        Operator::Power => BinaryOpNode::eval_power(result, arg),
+                        Operator::BitAnd => BinaryOpNode::eval_bitand(result, arg),
+                        Operator::BitOr => BinaryOpNode::eval_bitor(result, arg),
+                        Operator::BitXor => BinaryOpNode::eval_bitxor(result, arg),
+                        Operator::Shl => BinaryOpNode::eval_shl(result, arg),
+                        Operator::Shr => BinaryOpNode::eval_shr(result, arg),
+                        Operator::And | Operator::Or => unreachable!(),
+                        Operator::Not | Operator::BitNot => Err(eval::Error::new(&format!(
+                            "`{}` cannot be used as a binary operator", op.symbol()
+                        ))),
+                    }
+                },
+            });
         }
         Ok(result)
     }
 }
 
+// Short-circuiting logical operators.
+impl BinaryOpNode {
+    /// Evaluate the "&&" operator for two values, without evaluating
+    /// the right-hand side unless the left-hand side is `true`.
+    fn eval_and(context: &Context, left: Value, right: &Box<Eval>) -> eval::Result {
+        if let Value::Boolean(left) = left {
+            if !left {
+                return Ok(Value::Boolean(false));
+            }
+            return match try!(right.eval(&context)) {
+                Value::Boolean(right) => Ok(Value::Boolean(right)),
+                right => BinaryOpNode::err("&&", Value::Boolean(left), right),
+            };
+        }
+        BinaryOpNode::err("&&", left, Value::Empty)
+    }
+
+    /// Evaluate the "||" operator for two values, without evaluating
+    /// the right-hand side unless the left-hand side is `false`.
+    fn eval_or(context: &Context, left: Value, right: &Box<Eval>) -> eval::Result {
+        if let Value::Boolean(left) = left {
+            if left {
+                return Ok(Value::Boolean(true));
+            }
+            return match try!(right.eval(&context)) {
+                Value::Boolean(right) => Ok(Value::Boolean(right)),
+                right => BinaryOpNode::err("||", Value::Boolean(left), right),
+            };
+        }
+        BinaryOpNode::err("||", left, Value::Empty)
+    }
+}
+
 // Comparison operators.
 impl BinaryOpNode {
     /// Evaluate the "<" operator for two values.
@@ -89,6 +150,9 @@ impl BinaryOpNode {
         eval2!((left: Integer, right: Float) -> Boolean { (left as FloatRepr) < right });
         eval2!((left: Float, right: Integer) -> Boolean { left < (right as FloatRepr) });
         eval2!((left: Float, right: Float) -> Boolean { left < right });
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return Ok(Value::Boolean(ln * rd < rn * ld));
+        }
         BinaryOpNode::err("<", left, right)
     }
 
@@ -98,6 +162,9 @@ impl BinaryOpNode {
         eval2!((left: Integer, right: Float) -> Boolean { (left as FloatRepr) <= right });
         eval2!((left: Float, right: Integer) -> Boolean { left <= (right as FloatRepr) });
         eval2!((left: Float, right: Float) -> Boolean { left <= right });
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return Ok(Value::Boolean(ln * rd <= rn * ld));
+        }
         BinaryOpNode::err("<=", left, right)
     }
 
@@ -107,6 +174,9 @@ impl BinaryOpNode {
         eval2!((left: Integer, right: Float) -> Boolean { (left as FloatRepr) > right });
         eval2!((left: Float, right: Integer) -> Boolean { left > (right as FloatRepr) });
         eval2!((left: Float, right: Float) -> Boolean { left > right });
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return Ok(Value::Boolean(ln * rd > rn * ld));
+        }
         BinaryOpNode::err(">", left, right)
     }
 
@@ -116,6 +186,9 @@ impl BinaryOpNode {
         eval2!((left: Integer, right: Float) -> Boolean { (left as FloatRepr) >= right });
         eval2!((left: Float, right: Integer) -> Boolean { left >= (right as FloatRepr) });
         eval2!((left: Float, right: Float) -> Boolean { left >= right });
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return Ok(Value::Boolean(ln * rd >= rn * ld));
+        }
         BinaryOpNode::err(">=", left, right)
     }
 
@@ -126,6 +199,15 @@ impl BinaryOpNode {
         eval2!((left: Integer, right: Float) -> Boolean { (left as FloatRepr) == right });
         eval2!((left: Float, right: Integer) -> Boolean { left == (right as FloatRepr) });
         eval2!((left: Float, right: Float) -> Boolean { left == right });
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return Ok(Value::Boolean(ln * rd == rn * ld));
+        }
+        // complex values only support equality, never ordering
+        if is_complex(&left) || is_complex(&right) {
+            if let (Some((lr, li)), Some((rr, ri))) = (as_complex(&left), as_complex(&right)) {
+                return Ok(Value::Boolean(lr == rr && li == ri));
+            }
+        }
 
         // others
         eval2!((left: &Array, right: &Array) -> Boolean { left == right });
@@ -142,6 +224,14 @@ impl BinaryOpNode {
         eval2!((left: Integer, right: Float) -> Boolean { (left as FloatRepr) != right });
         eval2!((left: Float, right: Integer) -> Boolean { left != (right as FloatRepr) });
         eval2!((left: Float, right: Float) -> Boolean { left != right });
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return Ok(Value::Boolean(ln * rd != rn * ld));
+        }
+        if is_complex(&left) || is_complex(&right) {
+            if let (Some((lr, li)), Some((rr, ri))) = (as_complex(&left), as_complex(&right)) {
+                return Ok(Value::Boolean(lr != rr || li != ri));
+            }
+        }
 
         // others
         eval2!((left: &Array, right: &Array) -> Boolean { left != right });
@@ -165,9 +255,17 @@ impl BinaryOpNode {
 // Other binary operators.
 impl BinaryOpNode {
     /// Evaluate the "+" operator for two values.
-    fn eval_plus(left: Value, right: Value) -> eval::Result {
+    fn eval_plus(context: &Context, left: Value, right: Value) -> eval::Result {
         eval2!(left, right : &String { left.clone() + &*right });
-        eval2!(left, right : Integer { left + right });
+        if context.checked_arithmetic() {
+            eval2!(left, right : Integer {{
+                try!(left.checked_add(right).ok_or_else(|| eval::Error::IntegerOverflow{
+                    operation: "+".to_string(),
+                }))
+            }});
+        } else {
+            eval2!(left, right : Integer { left + right });
+        }
         eval2!(left, right : Float { left + right });
         eval2!((left: Integer, right: Float) -> Float { left as FloatRepr + right });
         eval2!((left: Float, right: Integer) -> Float { left + right as FloatRepr });
@@ -187,21 +285,55 @@ impl BinaryOpNode {
             left
         }});
 
+        // rest of the numeric tower: Integer (above) is a subset of
+        // Rational, which is a subset of Float (above), which is
+        // a subset of Complex
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return make_rational(ln * rd + rn * ld, ld * rd);
+        }
+        if let Some(((lr, li), (rr, ri))) = as_complex_pair(&left, &right) {
+            return Ok(Value::Complex(lr + rr, li + ri));
+        }
+
         BinaryOpNode::err("+", left, right)
     }
 
     /// Evaluate the "-" operator for two values.
-    fn eval_minus(left: Value, right: Value) -> eval::Result {
-        eval2!(left, right : Integer { left - right });
+    fn eval_minus(context: &Context, left: Value, right: Value) -> eval::Result {
+        if context.checked_arithmetic() {
+            eval2!(left, right : Integer {{
+                try!(left.checked_sub(right).ok_or_else(|| eval::Error::IntegerOverflow{
+                    operation: "-".to_string(),
+                }))
+            }});
+        } else {
+            eval2!(left, right : Integer { left - right });
+        }
         eval2!(left, right : Float { left - right });
         eval2!((left: Integer, right: Float) -> Float { left as FloatRepr - right });
         eval2!((left: Float, right: Integer) -> Float { left - right as FloatRepr });
+
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return make_rational(ln * rd - rn * ld, ld * rd);
+        }
+        if let Some(((lr, li), (rr, ri))) = as_complex_pair(&left, &right) {
+            return Ok(Value::Complex(lr - rr, li - ri));
+        }
+
         BinaryOpNode::err("-", left, right)
     }
 
     /// Evaluate the "*" operator for two values.
-    fn eval_times(left: Value, right: Value) -> eval::Result {
-        eval2!(left, right : Integer { left * right });
+    fn eval_times(context: &Context, left: Value, right: Value) -> eval::Result {
+        if context.checked_arithmetic() {
+            eval2!(left, right : Integer {{
+                try!(left.checked_mul(right).ok_or_else(|| eval::Error::IntegerOverflow{
+                    operation: "*".to_string(),
+                }))
+            }});
+        } else {
+            eval2!(left, right : Integer { left * right });
+        }
         eval2!(left, right : Float { left * right });
 
         // multiplying string/array by a number is repeating (like in Python)
@@ -218,6 +350,13 @@ impl BinaryOpNode {
             return api::strings::join(left, right);
         }
 
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return make_rational(ln * rn, ld * rd);
+        }
+        if let Some(((lr, li), (rr, ri))) = as_complex_pair(&left, &right) {
+            return Ok(Value::Complex(lr * rr - li * ri, lr * ri + li * rr));
+        }
+
         BinaryOpNode::err("*", left, right)
     }
 
@@ -231,6 +370,20 @@ impl BinaryOpNode {
             return api::strings::split(left, right);
         }
 
+        if let Some((ln, ld, rn, rd)) = as_rational_pair(&left, &right) {
+            return make_rational(ln * rd, ld * rn);
+        }
+        if let Some(((lr, li), (rr, ri))) = as_complex_pair(&left, &right) {
+            let denom = rr * rr + ri * ri;
+            if denom == 0.0 {
+                return Err(eval::Error::new("division by zero complex value"));
+            }
+            return Ok(Value::Complex(
+                (lr * rr + li * ri) / denom,
+                (li * rr - lr * ri) / denom,
+            ));
+        }
+
         BinaryOpNode::err("/", left, right)
     }
 
@@ -258,9 +411,7 @@ impl BinaryOpNode {
     fn eval_power(left: Value, right: Value) -> eval::Result {
         eval2!(left, right : Integer {{
             if right > (u32::max_value() as IntegerRepr) {
-                return Err(eval::Error::new(&format!(
-                    "exponent out of range: {}", right
-                )));
+                return Err(eval::Error::ExponentOutOfRange(right));
             }
             left.pow(right as u32)
         }});
@@ -270,22 +421,183 @@ impl BinaryOpNode {
         });
         eval2!((left: Float, right: Integer) -> Float {{
             if right > (i32::max_value() as IntegerRepr) {
-                return Err(eval::Error::new(&format!(
-                    "exponent out of range: {}", right
-                )));
+                return Err(eval::Error::ExponentOutOfRange(right));
             }
             left.powi(right as i32)
         }});
 
+        // a Rational raised to a non-negative integer power stays exact;
+        // any other power with a Rational operand is computed only when
+        // the other operand is Complex (below), and is rejected otherwise
+        if is_rational(&left) && !is_complex(&right) {
+            if let (Some((n, d)), Value::Integer(exp)) = (as_rational(&left), right.clone()) {
+                if exp >= 0 {
+                    return make_rational(n.pow(exp as u32), d.pow(exp as u32));
+                }
+            }
+        }
+
+        // Complex exponentiation (via the polar-form equivalent of `powc`);
+        // only a real-valued exponent is supported.
+        if is_complex(&left) || is_complex(&right) {
+            if let (Some((re, im)), Some((exp, exp_im))) = (as_complex(&left), as_complex(&right)) {
+                if exp_im == 0.0 {
+                    let modulus = (re * re + im * im).sqrt();
+                    let angle = im.atan2(re);
+                    let new_modulus = modulus.powf(exp);
+                    let new_angle = angle * exp;
+                    return Ok(Value::Complex(
+                        new_modulus * new_angle.cos(),
+                        new_modulus * new_angle.sin(),
+                    ));
+                }
+            }
+        }
+
         BinaryOpNode::err("**", left, right)
     }
 
+    /// Evaluate the "&" (bitwise AND) operator for two values.
+    fn eval_bitand(left: Value, right: Value) -> eval::Result {
+        eval2!(left, right : Integer { left & right });
+        BinaryOpNode::err("&", left, right)
+    }
+
+    /// Evaluate the "|" (bitwise OR) operator for two values.
+    fn eval_bitor(left: Value, right: Value) -> eval::Result {
+        eval2!(left, right : Integer { left | right });
+        BinaryOpNode::err("|", left, right)
+    }
+
+    /// Evaluate the "^" (bitwise XOR) operator for two values.
+    fn eval_bitxor(left: Value, right: Value) -> eval::Result {
+        eval2!(left, right : Integer { left ^ right });
+        BinaryOpNode::err("^", left, right)
+    }
+
+    /// Evaluate the "<<" (left shift) operator for two values.
+    fn eval_shl(left: Value, right: Value) -> eval::Result {
+        eval2!(left, right : Integer {{
+            try!(BinaryOpNode::check_shift_count(right));
+            left << (right as u32)
+        }});
+        BinaryOpNode::err("<<", left, right)
+    }
+
+    /// Evaluate the ">>" (right shift) operator for two values.
+    fn eval_shr(left: Value, right: Value) -> eval::Result {
+        eval2!(left, right : Integer {{
+            try!(BinaryOpNode::check_shift_count(right));
+            left >> (right as u32)
+        }});
+        BinaryOpNode::err(">>", left, right)
+    }
+
+    /// Make sure a shift count is non-negative and within the bit width
+    /// of `IntegerRepr`, the same way `eval_power` guards its exponent.
+    fn check_shift_count(count: IntegerRepr) -> Result<(), eval::Error> {
+        let bits = (::std::mem::size_of::<IntegerRepr>() * 8) as IntegerRepr;
+        if count < 0 || count >= bits {
+            return Err(eval::Error::ExponentOutOfRange(count));
+        }
+        Ok(())
+    }
+
     /// Produce an error about invalid arguments for an operator.
     fn err(op: &str, left: Value, right: Value) -> eval::Result {
-        Err(eval::Error::new(&format!(
-            "invalid arguments for `{}` operator: `{:?}` and `{:?}`",
-            op, left, right)))
+        Err(eval::Error::WrongTypeCombination{
+            operator: op.to_string(),
+            expected: "matching numeric, string, or array types".to_string(),
+            actual: vec![format!("{:?}", left), format!("{:?}", right)],
+        })
+    }
+}
+
+
+// The numeric tower: `Integer` is a subset of `Rational`, which is
+// a subset of `Float`, which is a subset of `Complex`. The helpers below
+// let the arithmetic/comparison operators above promote an Integer/Rational
+// pair (with at least one Rational) to `Rational`, and any numeric pair with
+// at least one `Complex` to `Complex`. A `Rational` mixed with a `Float` is
+// not promoted, and the operators reject that pair.
+
+/// View a value as a `(numerator, denominator)` pair if it's an `Integer`
+/// or a `Rational`.
+fn as_rational(value: &Value) -> Option<(IntegerRepr, IntegerRepr)> {
+    match *value {
+        Value::Integer(i) => Some((i, 1)),
+        Value::Rational(n, d) => Some((n, d)),
+        _ => None,
+    }
+}
+
+/// Like `as_rational`, but for both operands at once; only returns
+/// something if at least one of them is actually a `Rational`
+/// (otherwise plain `Integer` arithmetic elsewhere takes priority).
+fn as_rational_pair(left: &Value, right: &Value) -> Option<(IntegerRepr, IntegerRepr, IntegerRepr, IntegerRepr)> {
+    if is_complex(left) || is_complex(right) {
+        return None;
+    }
+    if !is_rational(left) && !is_rational(right) {
+        return None;
+    }
+    match (as_rational(left), as_rational(right)) {
+        (Some((ln, ld)), Some((rn, rd))) => Some((ln, ld, rn, rd)),
+        _ => None,
+    }
+}
+
+/// View a value as a `(real, imaginary)` pair if it's numeric at all.
+fn as_complex(value: &Value) -> Option<(FloatRepr, FloatRepr)> {
+    match *value {
+        Value::Integer(i) => Some((i as FloatRepr, 0.0)),
+        Value::Float(f) => Some((f, 0.0)),
+        Value::Rational(n, d) => Some((n as FloatRepr / d as FloatRepr, 0.0)),
+        Value::Complex(re, im) => Some((re, im)),
+        _ => None,
+    }
+}
+
+/// Like `as_complex`, but for both operands at once; only returns
+/// something if at least one of them is actually `Complex`.
+fn as_complex_pair(left: &Value, right: &Value) -> Option<((FloatRepr, FloatRepr), (FloatRepr, FloatRepr))> {
+    if !is_complex(left) && !is_complex(right) {
+        return None;
+    }
+    match (as_complex(left), as_complex(right)) {
+        (Some(l), Some(r)) => Some((l, r)),
+        _ => None,
+    }
+}
+
+fn is_rational(value: &Value) -> bool {
+    match *value { Value::Rational(..) => true, _ => false }
+}
+
+fn is_complex(value: &Value) -> bool {
+    match *value { Value::Complex(..) => true, _ => false }
+}
+
+fn gcd(a: IntegerRepr, b: IntegerRepr) -> IntegerRepr {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        let t = b;
+        b = a % b;
+        a = t;
+    }
+    a
+}
+
+/// Build a `Rational` value out of a `num/den` pair, reduced to lowest
+/// terms with a positive denominator. Errors on a zero denominator.
+fn make_rational(num: IntegerRepr, den: IntegerRepr) -> eval::Result {
+    if den == 0 {
+        return Err(eval::Error::new("division by zero in rational value"));
     }
+    let sign = if den < 0 { -1 } else { 1 };
+    let (num, den) = (num * sign, den * sign);
+    let divisor = match gcd(num, den) { 0 => 1, g => g };
+    Ok(Value::Rational(num / divisor, den / divisor))
 }
 
 
@@ -301,9 +613,7 @@ impl Eval for ConditionalNode {
                 self.else_.eval(&context)
             }
         } else {
-            Err(eval::Error::new(&format!(
-                "expected a boolean condition, got {} instead", cond_type
-            )))
+            Err(eval::Error::ExpectedBoolean{actual: cond_type.to_string()})
         }
     }
 }
@@ -1,8 +1,31 @@
 //! Module implementing the evaluation of postfix operators.
 
-use ast::{FunctionCallNode, SubscriptNode};
+use unicode_segmentation::UnicodeSegmentation;
+
+use ast::{FunctionCallNode, IndexNode, PipelineNode, RangeNode};
 
 use eval::{self, Context, Eval, Value};
+use eval::model::value::{IntegerRepr, MapRepr, ObjectRepr};
+
+
+/// Evaluate a `start:end:step` slice spec into a `Value::Range`.
+impl Eval for RangeNode {
+    fn eval(&self, context: &Context) -> eval::Result {
+        let part = |part: &Option<Box<Eval>>| -> Result<Option<IntegerRepr>, eval::Error> {
+            match *part {
+                Some(ref expr) => match try!(expr.eval(&context)) {
+                    Value::Integer(i) => Ok(Some(i)),
+                    v => Err(eval::Error::NotIndexable{value_type: v.typename().to_string()}),
+                },
+                None => Ok(None),
+            }
+        };
+        let start = try!(part(&self.start));
+        let end = try!(part(&self.end));
+        let step = try!(part(&self.step));
+        Ok(Value::Range(start, end, step))
+    }
+}
 
 
 /// Evaluate the function call AST node.
@@ -22,67 +45,165 @@ impl Eval for FunctionCallNode {
 }
 
 
-/// Evaluate the array subscripting AST node.
-impl Eval for SubscriptNode {
+/// Evaluate the `subject | name(args...)` pipeline AST node.
+///
+/// The piped-in `subject` is threaded through as the first argument of
+/// the call, so `x | f(a, b)` is equivalent to `f(x, a, b)`, and a bare
+/// `x | f` is equivalent to the unary call `f(x)`.
+impl Eval for PipelineNode {
+    fn eval(&self, context: &Context) -> eval::Result {
+        let subject = try!(self.subject.eval(&context));
+
+        let mut args = Vec::with_capacity(self.args.len() + 1);
+        args.push(subject);
+        for arg in &self.args {
+            args.push(try!(arg.eval(&context)));
+        }
+
+        context.call_func(&self.name, args)
+    }
+}
+
+
+/// Evaluate the `subject[index]` AST node.
+impl Eval for IndexNode {
     fn eval(&self, context: &Context) -> eval::Result {
-        let object = try!(self.object.eval(&context));
+        let subject = try!(self.subject.eval(&context));
         let index = try!(self.index.eval(&context));
 
-        match object {
-            Value::Array(ref a) => SubscriptNode::eval_on_array(&a, &index),
-            Value::String(ref s) => SubscriptNode::eval_on_string(&s, &index),
-            _ => Err(eval::Error::new(
-                &format!("can't index {:?} with {:?}", object, index)
-            )),
+        match subject {
+            Value::Array(ref a) => IndexNode::eval_on_array(a, &index),
+            Value::String(ref s) => IndexNode::eval_on_string(s, &index),
+            Value::Object(ref o) => IndexNode::eval_on_object(o, &index),
+            Value::Map(ref m) => IndexNode::eval_on_map(m, &index),
+            _ => Err(eval::Error::NotIndexable{value_type: subject.typename().to_string()}),
         }
     }
 }
-impl SubscriptNode {
-    // TODO(xion): consider supporting Python-style negative indices
+impl IndexNode {
+    /// Resolve a (possibly negative) index against a collection length,
+    /// the way Python counts `-1` as the last element.
+    fn resolve_index(i: IntegerRepr, length: usize) -> Result<usize, eval::Error> {
+        let resolved = if i < 0 { i + (length as IntegerRepr) } else { i };
+        if resolved < 0 || resolved as usize >= length {
+            return Err(eval::Error::IndexOutOfBounds{index: i, length: length});
+        }
+        Ok(resolved as usize)
+    }
 
     fn eval_on_array(array: &Vec<Value>, index: &Value) -> eval::Result {
         match *index {
             Value::Integer(i) => {
-                if i < 0 {
-                    return Err(eval::Error::new(
-                        &format!("array index cannot be negative; got {}", i)
-                    ));
-                }
-                let idx = i as usize;
-                if idx >= array.len() {
-                    return Err(eval::Error::new(
-                        &format!("array index out of range ({})", i)
-                    ));
-                }
+                let idx = try!(IndexNode::resolve_index(i, array.len()));
                 // TODO(xion): the clone below is very inefficient for
                 // multi-dimensional arrays; return some Value pointer instead
                 Ok(array[idx].clone())
             },
-            Value::Float(..) => Err(eval::Error::new(
-                &format!("array indices must be integers")
-            )),
-            _ => Err(eval::Error::new(
-                &format!("can't index an array with {:?}", index)
-            )),
+            Value::Range(start, end, step) => {
+                let step = try!(IndexNode::resolve_step(step));
+                let (start, end) = IndexNode::resolve_slice_bounds(start, end, step, array.len());
+                let indices = IndexNode::slice_indices(start, end, step);
+                Ok(Value::Array(indices.into_iter().map(|i| array[i].clone()).collect()))
+            },
+            _ => Err(eval::Error::NotIndexable{value_type: "array".to_string()}),
         }
     }
 
+    /// Index by grapheme cluster (user-perceived character), not by
+    /// `char`, so e.g. accented letters formed of multiple codepoints
+    /// are treated as a single element; see `api::chars` for codepoint
+    /// access instead.
     fn eval_on_string(string: &String, index: &Value) -> eval::Result {
+        let graphemes: Vec<&str> = UnicodeSegmentation::graphemes(string.as_str(), true).collect();
         match *index {
             Value::Integer(i) => {
-                string.chars().nth(i as usize)
-                    .ok_or_else(|| eval::Error::new(
-                        &format!("character index out of range: {}", i)
-                    ))
-                    .map(|c| {
-                        let mut result = String::new();
-                        result.push(c);
-                        Value::String(result)
-                    })
+                let idx = try!(IndexNode::resolve_index(i, graphemes.len()));
+                Ok(Value::String(graphemes[idx].to_string()))
+            },
+            Value::Range(start, end, step) => {
+                let step = try!(IndexNode::resolve_step(step));
+                let (start, end) = IndexNode::resolve_slice_bounds(start, end, step, graphemes.len());
+                let indices = IndexNode::slice_indices(start, end, step);
+                Ok(Value::String(indices.into_iter().map(|i| graphemes[i]).collect()))
+            },
+            _ => Err(eval::Error::NotIndexable{value_type: "string".to_string()}),
+        }
+    }
+
+    /// Resolve a `start`/`end` slice bound (either may be negative, missing,
+    /// or past either end of the collection) into a concrete bound pair,
+    /// mirroring CPython's `slice.indices()`: for a positive step this is
+    /// the usual `[start, end)`, but for a negative step `start` is the
+    /// higher, inclusive bound and `end` the lower, exclusive one (so
+    /// `end` may legitimately clamp down to `-1`, meaning "include index
+    /// zero"). `_[0:1000]` yields the whole sequence and `_[::-1]` reverses
+    /// it, rather than either one erroring.
+    fn resolve_slice_bounds(start: Option<IntegerRepr>, end: Option<IntegerRepr>,
+                              step: IntegerRepr, length: usize) -> (IntegerRepr, IntegerRepr) {
+        let length = length as IntegerRepr;
+        let (lower, upper) = if step > 0 { (0, length) } else { (-1, length - 1) };
+
+        let clamp = |i: IntegerRepr| -> IntegerRepr {
+            let i = if i < 0 { i + length } else { i };
+            if i < lower { lower } else if i > upper { upper } else { i }
+        };
+
+        let start = match start {
+            Some(i) => clamp(i),
+            None => if step < 0 { upper } else { lower },
+        };
+        let end = match end {
+            Some(i) => clamp(i),
+            None => if step < 0 { lower } else { upper },
+        };
+        (start, end)
+    }
+
+    fn resolve_step(step: Option<IntegerRepr>) -> Result<IntegerRepr, eval::Error> {
+        match step.unwrap_or(1) {
+            0 => Err(eval::Error::new("slice step cannot be zero")),
+            step => Ok(step),
+        }
+    }
+
+    /// Enumerate the indices a `start`/`end` slice with the given step
+    /// covers: for a positive step, `start` (inclusive) up to `end`
+    /// (exclusive); for a negative one, `start` (inclusive) down to `end`
+    /// (exclusive), walking backwards.
+    fn slice_indices(start: IntegerRepr, end: IntegerRepr, step: IntegerRepr) -> Vec<usize> {
+        let mut indices = Vec::new();
+        let mut i = start;
+        if step > 0 {
+            while i < end {
+                indices.push(i as usize);
+                i += step;
+            }
+        } else {
+            while i > end {
+                indices.push(i as usize);
+                i += step;
+            }
+        }
+        indices
+    }
+
+    fn eval_on_object(object: &ObjectRepr, index: &Value) -> eval::Result {
+        match *index {
+            Value::String(ref key) => {
+                object.get(key).cloned()
+                    .ok_or_else(|| eval::Error::KeyNotFound(key.clone()))
+            },
+            _ => Err(eval::Error::NotIndexable{value_type: "object".to_string()}),
+        }
+    }
+
+    fn eval_on_map(map: &MapRepr, index: &Value) -> eval::Result {
+        match *index {
+            Value::String(ref key) => {
+                map.get(key).cloned()
+                    .ok_or_else(|| eval::Error::KeyNotFound(key.clone()))
             },
-            _ => Err(eval::Error::new(
-                &format!("can't index a string with {:?}", index)
-            )),
+            _ => Err(eval::Error::NotIndexable{value_type: "map".to_string()}),
         }
     }
 }
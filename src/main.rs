@@ -26,8 +26,8 @@ fn main() {
     let mut options = Options::new();
     options.optflag("h", "help", "Show this usage message");
     options.optflag("p", "parse", "Only parse the expression, printing AST");
-    // TODO(xion): -l flag that causes input to be interpreted as single array of lines
-    // rather make the expression execute againts each line individually
+    options.optflag("c", "checked", "Error out on integer overflow instead of wrapping");
+    options.optflag("l", "lines", "Evaluate the expression once, against an array of all input lines");
 
     let args = options.parse(&argv[1..]).unwrap();
     if args.opt_present("h") {
@@ -48,7 +48,19 @@ fn main() {
             Err(error) => { error!("{:?}", error); exit(1); },
         }
     } else {
-        if let Err(error) = ap::apply(expr, io::stdin(), &mut io::stdout()) {
+        // in "lines" mode, the whole input is read upfront and handed to
+        // the expression once as a single Value::Array of lines, rather
+        // than running the expression separately against each line.
+        // apply_lines_checked() lives in the ap library crate alongside
+        // apply_checked()/parse() above -- same as those, its definition
+        // is outside this source tree.
+        let checked = args.opt_present("c");
+        let result = if args.opt_present("l") {
+            ap::apply_lines_checked(expr, checked, io::stdin(), &mut io::stdout())
+        } else {
+            ap::apply_checked(expr, checked, io::stdin(), &mut io::stdout())
+        };
+        if let Err(error) = result {
             error!("{:?}", error);
             exit(1);
         }
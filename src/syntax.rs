@@ -1,85 +1,416 @@
 use std::str::from_utf8;
 
-use nom::{alphanumeric, multispace, IResult, Needed, Err, ErrorKind};
+use nom::{alphanumeric, multispace, Err, ErrorKind, IResult, Needed};
 
-use eval::{Eval, Context, Value};
+use ast::{self, Operator};
+use eval::{self, Context, Eval};
 
 
-struct ValueNode {
-    pub value: Value,
+/// The `primary ?? default` fallback operator: evaluates `primary` and,
+/// if that fails, evaluates and returns `default` instead -- the way
+/// `try(expr, default)` would in a language with ordinary function calls.
+/// This sits outside `ast.rs` since nothing else in the grammar needs it.
+struct FallbackNode {
+    pub primary: Box<Eval>,
+    pub default: Box<Eval>,
 }
-impl Eval for ValueNode {
-    fn eval(&self, context: &Context) -> Value {
-        context.get(&self.value).unwrap_or(&self.value).clone()
+impl Eval for FallbackNode {
+    fn eval(&self, context: &Context) -> eval::Result {
+        match self.primary.eval(&context) {
+            Ok(value) => Ok(value),
+            Err(_) => self.default.eval(&context),
+        }
+    }
+}
+
+
+/// A generic "no match here" parse error, for the hand-written functions
+/// below that don't go through a `named!`-generated parser of their own.
+fn no_match<'a, T>(input: &'a [u8]) -> IResult<&'a [u8], T> {
+    IResult::Error(Err::Position(ErrorKind::Alt, input))
+}
+
+fn to_string(bytes: &[u8]) -> Result<String, ::std::str::Utf8Error> {
+    from_utf8(bytes).map(str::to_string)
+}
+
+fn is_word_char(c: u8) -> bool {
+    (c as char).is_alphanumeric() || c == b'_' || c == b'.'
+}
+
+/// An identifier: `_` or a run of alphanumerics, used for function/
+/// variable names.
+named!(ident<&[u8], String>, map_res!(
+    alt!(tag!("_") | alphanumeric), to_string
+));
+
+/// A value literal token: `_`, or a run of "word" characters, wide enough
+/// to cover integers, floats and barewords alike. `ast::ValueNode`'s own
+/// `FromStr` (which defers to `Value`'s) decides what it actually is.
+named!(literal_token<&[u8], &[u8]>, alt!(tag!("_") | take_while1!(is_word_char)));
+
+fn literal(input: &[u8]) -> IResult<&[u8], ast::ValueNode> {
+    match literal_token(input) {
+        IResult::Done(rest, token) => {
+            match from_utf8(token).ok().and_then(|s| s.parse::<ast::ValueNode>().ok()) {
+                Some(node) => IResult::Done(rest, node),
+                None => no_match(input),
+            }
+        },
+        IResult::Incomplete(n) => IResult::Incomplete(n),
+        IResult::Error(e) => IResult::Error(e),
     }
 }
 
-struct BinaryOpNode {
-    pub op: String,  // TODO(xion): enum?
-    pub left: Box<Eval>,
-    pub right: Box<Eval>,
+/// Skip over any whitespace, discarding it.
+fn skip_space(input: &[u8]) -> &[u8] {
+    match multispace(input) {
+        IResult::Done(rest, _) => rest,
+        _ => input,
+    }
 }
-impl Eval for BinaryOpNode {
-    fn eval(&self, context: &Context) -> Value {
-        match &self.op[..] {
-            "+" => {
-                // TODO(xion): string concatenation vs. adding numbers
-                self.left.eval(&context) + &self.right.eval(&context)
+
+/// A parenthesized, comma-separated argument list: `(arg, arg, ...)`.
+/// Assumes `input` starts right at the opening `(`; shared by `call()`
+/// (`name(args)`) and `pipeline_suffix()` (`subject | name(args)`).
+fn arg_list(input: &[u8]) -> IResult<&[u8], Vec<Box<Eval>>> {
+    let mut rest = skip_space(&input[1..]);
+    let mut args = Vec::new();
+    if rest.first() == Some(&b')') {
+        rest = &rest[1..];
+    } else {
+        loop {
+            match expr(rest) {
+                IResult::Done(after_arg, arg) => {
+                    args.push(arg);
+                    rest = skip_space(after_arg);
+                },
+                IResult::Incomplete(n) => return IResult::Incomplete(n),
+                IResult::Error(e) => return IResult::Error(e),
+            }
+            match rest.first() {
+                Some(&b',') => { rest = skip_space(&rest[1..]); },
+                Some(&b')') => { rest = &rest[1..]; break; },
+                _ => return no_match(input),
             }
-            // TODO(xion): other operators
-            _ => panic!("unknown operator: {}", self.op)
         }
     }
+    IResult::Done(rest, args)
 }
 
+/// `name(arg, arg, ...)`, producing an `ast::FunctionCallNode` -- this is
+/// what makes `context.call_func`, and therefore the whole stdlib in
+/// `eval::api`, reachable from a parsed expression at all.
+fn call(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
+    let (after_name, name) = match ident(input) {
+        IResult::Done(rest, name) => (rest, name),
+        IResult::Incomplete(n) => return IResult::Incomplete(n),
+        IResult::Error(e) => return IResult::Error(e),
+    };
 
-named!(value<&[u8], ValueNode>, chain!(
-    value: map_res!(alt!(tag!("_") | alphanumeric), from_utf8),
-    || { ValueNode{value: value.to_string()} }
-));
-named!(binary_op<&[u8], BinaryOpNode>, chain!(
-    left: value ~
-    multispace? ~
-    op: map_res!(is_a!("+"), from_utf8) ~
-    multispace? ~
-    right: value,
-    || { BinaryOpNode{op: op.to_string(),
-                      left: Box::new(left),
-                      right: Box::new(right)} }
-));
+    let after_name = skip_space(after_name);
+    if after_name.first() != Some(&b'(') {
+        return no_match(input);
+    }
 
-fn expr(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
-    // TODO(xion): figure out how to do this with alt!() rather than manually
-    // (the problem with alt! is that it uses `match` for branching
-    // and that doesn't work since *Node results are unrelated types and cannot
-    // be matched against)
-    if let IResult::Done(input, output) = binary_op(input) {
-        assert!(input.is_empty());
-        return IResult::Done(input, Box::new(output) as Box<Eval>);
+    let (rest, args) = match arg_list(after_name) {
+        IResult::Done(rest, args) => (rest, args),
+        IResult::Incomplete(n) => return IResult::Incomplete(n),
+        IResult::Error(e) => return IResult::Error(e),
+    };
+
+    IResult::Done(rest, Box::new(ast::FunctionCallNode{name: name, args: args}) as Box<Eval>)
+}
+
+/// A parenthesized `(expr)`, for grouping.
+fn parenthesized(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
+    if input.first() != Some(&b'(') {
+        return no_match(input);
     }
-    if let IResult::Done(input, output) = value(input) {
-        assert!(input.is_empty());
-        return IResult::Done(input, Box::new(output) as Box<Eval>);
+    let (rest, node) = match fallback_expr(skip_space(&input[1..])) {
+        IResult::Done(rest, node) => (rest, node),
+        IResult::Incomplete(n) => return IResult::Incomplete(n),
+        IResult::Error(e) => return IResult::Error(e),
+    };
+    let rest = skip_space(rest);
+    if rest.first() != Some(&b')') {
+        return no_match(input);
     }
+    IResult::Done(&rest[1..], node)
+}
 
-    // TODO(xion): introduce custom error type instead of the default numeric
-    IResult::Error(Err::Code(ErrorKind::Custom(404)))
+fn primary(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
+    let input = skip_space(input);
+    if let IResult::Done(rest, node) = parenthesized(input) {
+        return IResult::Done(rest, node);
+    }
+    if let IResult::Done(rest, node) = call(input) {
+        return IResult::Done(rest, node);
+    }
+    match literal(input) {
+        IResult::Done(rest, node) => IResult::Done(rest, Box::new(node) as Box<Eval>),
+        IResult::Incomplete(n) => IResult::Incomplete(n),
+        IResult::Error(e) => IResult::Error(e),
+    }
 }
 
+/// A `start:end` or `start:end:step` slice spec, or a plain index if no
+/// `:` is found. Assumes `input` starts right after the opening `[`.
+fn slice_or_index(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
+    let opt_part = |input: &[u8]| -> (&[u8], Option<Box<Eval>>) {
+        match expr(input) {
+            IResult::Done(rest, node) => (rest, Some(node)),
+            _ => (input, None),
+        }
+    };
+
+    let (rest, start) = opt_part(input);
+    let rest = skip_space(rest);
+    if rest.first() != Some(&b':') {
+        return match start {
+            Some(node) => IResult::Done(rest, node),
+            None => no_match(input),
+        };
+    }
 
-pub fn parse(input: &str) -> Box<Eval> {
-    match expr(input.trim().as_bytes()) {
-        IResult::Done(_, node) => node,
+    let (rest, end) = opt_part(skip_space(&rest[1..]));
+    let rest = skip_space(rest);
+    let (rest, step) = if rest.first() == Some(&b':') {
+        opt_part(skip_space(&rest[1..]))
+    } else {
+        (rest, None)
+    };
+
+    IResult::Done(rest, Box::new(ast::RangeNode{start: start, end: end, step: step}) as Box<Eval>)
+}
+
+/// `[index]` or `[slice]` right after a term, producing an `ast::IndexNode`.
+/// Assumes `input` starts right at the opening `[`.
+fn index_suffix(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
+    let (rest, index) = match slice_or_index(skip_space(&input[1..])) {
+        IResult::Done(rest, node) => (rest, node),
+        IResult::Incomplete(n) => return IResult::Incomplete(n),
+        IResult::Error(e) => return IResult::Error(e),
+    };
+    let rest = skip_space(rest);
+    if rest.first() != Some(&b']') {
+        return no_match(input);
+    }
+    IResult::Done(&rest[1..], index)
+}
+
+/// `| name` or `| name(args)` right after a term, returning the pipeline's
+/// callee name and arguments. Assumes `input` starts right at the `|`.
+///
+/// This is lexically indistinguishable from `Operator::BitOr` applied to a
+/// call expression -- `abs(x) | abs(y)` always parses as the pipeline
+/// `abs(abs(x), y)`, never as a bitwise-or of the two calls, since this
+/// postfix is tried before `parse_expr` ever gets to see the `|` as an
+/// operator token. A bitwise-or whose right-hand side is a call must be
+/// parenthesized (`abs(x) | (abs(y))`) to keep `ident()` below from
+/// matching and so fall through to `Operator::BitOr` instead.
+fn pipeline_suffix(input: &[u8]) -> IResult<&[u8], (String, Vec<Box<Eval>>)> {
+    // `||` is the short-circuiting "or" operator, not a pipe.
+    if input.get(1) == Some(&b'|') {
+        return no_match(input);
+    }
+    let (after_name, name) = match ident(skip_space(&input[1..])) {
+        IResult::Done(rest, name) => (rest, name),
+        IResult::Incomplete(n) => return IResult::Incomplete(n),
+        IResult::Error(e) => return IResult::Error(e),
+    };
+    // `ident` also accepts an all-digit run (so `call()` can report a
+    // sensible error on `2(3)` rather than not matching at all); here
+    // that would let a bitwise-or like `5 | 2` get mistaken for a
+    // pipeline to a function named "2", so require a real identifier.
+    match name.as_bytes().first() {
+        Some(&c) if c >= b'0' && c <= b'9' => return no_match(input),
+        _ => {},
+    }
+
+    let after_name = skip_space(after_name);
+    if after_name.first() != Some(&b'(') {
+        return IResult::Done(after_name, (name, Vec::new()));
+    }
+    match arg_list(after_name) {
+        IResult::Done(rest, args) => IResult::Done(rest, (name, args)),
+        IResult::Incomplete(n) => IResult::Incomplete(n),
+        IResult::Error(e) => IResult::Error(e),
+    }
+}
+
+/// A primary term, followed by zero or more `[index]` or `| name(...)`
+/// postfixes, left-associative (so `a[0][1]` and `a | f | g` both chain
+/// as expected). These bind tighter than any `ast::Operator`, and in
+/// particular consuming `| name` here is what keeps it from being
+/// swallowed by `Operator::BitOr`'s own `|` token in `parse_expr` below.
+fn postfix(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
+    let (mut rest, mut node) = match primary(input) {
+        IResult::Done(rest, node) => (rest, node),
+        other => return other,
+    };
+
+    loop {
+        let after_space = skip_space(rest);
+        if after_space.first() == Some(&b'[') {
+            match index_suffix(after_space) {
+                IResult::Done(after, index) => {
+                    node = Box::new(ast::IndexNode{subject: node, index: index}) as Box<Eval>;
+                    rest = after;
+                    continue;
+                },
+                IResult::Incomplete(n) => return IResult::Incomplete(n),
+                IResult::Error(_) => break,
+            }
+        }
+        if after_space.first() == Some(&b'|') {
+            match pipeline_suffix(after_space) {
+                IResult::Done(after, (name, args)) => {
+                    node = Box::new(ast::PipelineNode{subject: node, name: name, args: args}) as Box<Eval>;
+                    rest = after;
+                    continue;
+                },
+                IResult::Incomplete(n) => return IResult::Incomplete(n),
+                IResult::Error(_) => break,
+            }
+        }
+        break;
+    }
+
+    IResult::Done(rest, node)
+}
+
+/// A postfixed term, optionally preceded by a unary `!`/`~`/`-`/`+`.
+fn unary(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
+    let input = skip_space(input);
+    for &(token, op) in &[(b"!" as &[u8], Operator::Not), (b"~", Operator::BitNot),
+                          (b"-", Operator::Minus), (b"+", Operator::Plus)] {
+        if input.starts_with(token) {
+            let rest = skip_space(&input[token.len()..]);
+            return match unary(rest) {
+                IResult::Done(after, arg) =>
+                    IResult::Done(after, Box::new(ast::UnaryOpNode{op: op, arg: arg}) as Box<Eval>),
+                other => other,
+            };
+        }
+    }
+    postfix(input)
+}
+
+/// Recognize one binary operator token, longest match first so e.g.
+/// `<=` isn't mistaken for `<` followed by `=`.
+fn operator(input: &[u8]) -> IResult<&[u8], Operator> {
+    const TOKENS: &'static [&'static str] = &[
+        "**", "==", "!=", "<=", ">=", "&&", "||", "<<", ">>",
+        "<", ">", "+", "-", "*", "/", "%", "@", "&", "|", "^",
+    ];
+    for &token in TOKENS {
+        if input.starts_with(token.as_bytes()) {
+            if let Ok(op) = token.parse::<Operator>() {
+                return IResult::Done(&input[token.len()..], op);
+            }
+        }
+    }
+    no_match(input)
+}
+
+/// Precedence-climbing parser, using `ast::Operator`'s own `precedence()`
+/// so the binding strength here always matches what `operators.rs` assumes
+/// when it evaluates the `ast::BinaryOpNode` this builds. Operators parsed
+/// into the same `BinaryOpNode.rest` chain all have a precedence of at
+/// least `min_prec`, and never a higher one than the operator before them,
+/// so `BinaryOpNode::eval`'s left-to-right fold groups them correctly; a
+/// higher-precedence run is parsed first, recursively, into a single
+/// nested node that becomes one `rest` entry here -- which is also how
+/// `**`'s right-associativity falls out, by recursing at the *same* tier
+/// instead of one above it.
+fn parse_expr(input: &[u8], min_prec: u8) -> IResult<&[u8], Box<Eval>> {
+    let (mut rest, first) = match unary(input) {
+        IResult::Done(rest, node) => (rest, node),
+        other => return other,
+    };
+
+    let mut chain: Vec<(Operator, Box<Eval>)> = Vec::new();
+    loop {
+        let after_space = skip_space(rest);
+        let (op, after_op) = match operator(after_space) {
+            IResult::Done(after_op, op) => (op, after_op),
+            _ => break,
+        };
+        if op.precedence() < min_prec {
+            break;
+        }
+
+        let next_min_prec = if op == Operator::Power { op.precedence() } else { op.precedence() + 1 };
+        let after_op = skip_space(after_op);
+        let (after_right, right) = match parse_expr(after_op, next_min_prec) {
+            IResult::Done(after_right, node) => (after_right, node),
+            other => return other,
+        };
+
+        chain.push((op, right));
+        rest = after_right;
+    }
+
+    if chain.is_empty() {
+        IResult::Done(rest, first)
+    } else {
+        IResult::Done(rest, Box::new(ast::BinaryOpNode{first: first, rest: chain}) as Box<Eval>)
+    }
+}
+
+fn expr(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
+    parse_expr(input, 0)
+}
+
+/// `primary ?? default`, recursing on the right so `a ?? b ?? c` is
+/// `a ?? (b ?? c)`; binds looser than every `ast::Operator`, so it's
+/// parsed on top of a full `expr()` rather than folded into it.
+fn fallback_expr(input: &[u8]) -> IResult<&[u8], Box<Eval>> {
+    let (rest, primary) = match expr(input) {
+        IResult::Done(rest, node) => (rest, node),
+        other => return other,
+    };
+
+    let after_space = skip_space(rest);
+    if after_space.starts_with(b"??") {
+        let after_op = skip_space(&after_space[2..]);
+        return match fallback_expr(after_op) {
+            IResult::Done(after_default, default) => IResult::Done(
+                after_default,
+                Box::new(FallbackNode{primary: primary, default: default}) as Box<Eval>
+            ),
+            other => other,
+        };
+    }
+
+    IResult::Done(rest, primary)
+}
+
+
+pub fn parse(input: &str) -> Result<Box<Eval>, eval::Error> {
+    match fallback_expr(input.trim().as_bytes()) {
+        IResult::Done(rest, node) => {
+            if rest.is_empty() {
+                Ok(node)
+            } else {
+                Err(eval::Error::new(&format!(
+                    "unexpected trailing input: {:?}", from_utf8(rest)
+                )))
+            }
+        },
 
         IResult::Incomplete(Needed::Size(c)) => {
-            panic!("incomplete input, need {} more bytes", c);
+            Err(eval::Error::new(&format!(
+                "incomplete input, need {} more bytes", c
+            )))
         },
         IResult::Incomplete(Needed::Unknown) => {
-            panic!("incomplete input");
+            Err(eval::Error::new("incomplete input"))
         }
 
-        // TODO(xion): parse the error value and convert to custom error type,
-        // returning a Result<...> from this function
-        IResult::Error(e) => panic!("parse error: {:?}", e),
+        IResult::Error(e) => {
+            Err(eval::Error::new(&format!("parse error: {:?}", e)))
+        },
     }
 }
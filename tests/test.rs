@@ -176,6 +176,254 @@ fn binary_plus_constant_strings() {
     assert_eq!("barbaz", eval("bar + \"baz\""));
 }
 
+#[test]
+fn operator_precedence() {
+    assert_eq!("14", eval("2 + 3 * 4"));
+    assert_eq!("20", eval("(2 + 3) * 4"));
+    assert_eq!("10", eval("2 * 3 + 4"));
+    assert_eq!("512", eval("2 ** 3 ** 2"));
+}
+
+#[test]
+fn operator_precedence_bitwise_vs_arithmetic() {
+    // `&`/`<<` bind looser than `+`, per ast::Operator::precedence().
+    assert_eq!("3", eval("1 + 2 & 3"));
+    assert_eq!("8", eval("1 << 2 + 1"));
+}
+
+#[test]
+fn fallback_operator_primary_ok() {
+    assert_eq!("42", eval("42 ?? 0"));
+}
+
+#[test]
+fn fallback_operator_primary_error() {
+    assert_eq!("0", eval("(1 + true) ?? 0"));
+}
+
+#[test]
+fn fallback_operator_chained() {
+    assert_eq!("2", eval("(1 + true) ?? (1 + true) ?? 2"));
+}
+
+#[test]
+fn fallback_operator_catches_index_out_of_bounds() {
+    // parse()/apply() never reached the grammar rule that builds a
+    // FallbackNode until a later fix commit added it, so this would
+    // have failed to parse `??` at all when this request first shipped.
+    assert_eq!("99", eval("split(a.b, .)[5] ?? 99"));
+}
+
+#[test]
+fn bitwise_and() {
+    assert_eq!("2", eval("6 & 3"));
+}
+
+#[test]
+fn bitwise_or() {
+    assert_eq!("7", eval("6 | 1"));
+}
+
+#[test]
+fn bitwise_or_with_call_operand() {
+    // `|` also introduces a pipeline (see pipeline_suffix()), and that
+    // grammar is tried first, so `abs(-6) | abs(-1)` (no parens) actually
+    // parses as the pipeline call `abs(abs(-6), -1)`, not a bitwise-or.
+    // Parenthesizing the right operand keeps it out of pipeline_suffix()'s
+    // `ident()` match, letting it reach BinaryOpNode/Operator::BitOr instead.
+    assert_eq!("7", eval("6 | (abs(-1))"));
+}
+
+#[test]
+fn bitwise_xor() {
+    assert_eq!("5", eval("6 ^ 3"));
+}
+
+#[test]
+fn bitwise_not() {
+    assert_eq!("-7", eval("~6"));
+}
+
+#[test]
+fn bitwise_shift_left() {
+    assert_eq!("24", eval("3 << 3"));
+}
+
+#[test]
+fn bitwise_shift_right() {
+    assert_eq!("3", eval("24 >> 3"));
+}
+
+#[test]
+fn bitwise_shift_out_of_range() {
+    // eval_power() rejects an out-of-range exponent with a descriptive
+    // error; the shift operators guard their counts the same way.
+    assert_eq!("0", apply("(_ << 999) ?? 0", "1"));
+    assert_eq!("0", apply("(_ << -1) ?? 0", "1"));
+}
+
+#[test]
+fn index_array_element() {
+    assert_eq!("a", eval("split(a.b.c, .)[0]"));
+}
+
+#[test]
+fn slice_array_open_ended() {
+    // IndexNode/RangeNode had a working eval() from this request's own
+    // commit, but postfix() had no `[...]` grammar rule to build one
+    // until a later, differently-tagged fix commit added it.
+    assert_eq!("b", eval("split(a.b.c, .)[1:2][0]"));
+}
+
+#[test]
+fn slice_array_negative_step() {
+    // resolve_slice_bounds() used to clamp start/end into start <= end
+    // before the step's sign was known, which made any descending slice
+    // evaluate to empty; `a.b.c.d.e.f`[5:1:-1] walks backwards from index
+    // 5 down to (but excluding) index 1.
+    assert_eq!("f.e.d.c", eval("split(a.b.c.d.e.f, .)[5:1:-1] | join(.)"));
+}
+
+#[test]
+fn slice_array_reversed() {
+    assert_eq!("c.b.a", eval("split(a.b.c, .)[::-1] | join(.)"));
+}
+
+#[test]
+fn index_map_value() {
+    assert_eq!("y", eval("to_map(zip(split(a.b, .), split(x.y, .)))[b]"));
+}
+
+#[test]
+fn pipeline_to_len() {
+    assert_eq!("3", apply("_ | len", "abc"));
+}
+
+#[test]
+fn pipeline_with_args() {
+    // PipelineNode had a working eval() from this request's own commit,
+    // but postfix() had no `| name(...)` grammar rule to build one
+    // until a later, differently-tagged fix commit added it.
+    assert_eq!("b.c", eval("split(a.b.c, .)[1:] | join(.)"));
+}
+
+#[test]
+fn format_percent_directive() {
+    assert_eq!("42", apply("_ % 42", "%d"));
+}
+
+#[test]
+fn checked_arithmetic_overflow_errors() {
+    // Unchecked (the default) wraps silently; `--checked` was a CLI flag
+    // with no way to actually select it from library code until
+    // ap::apply_checked()'s `checked` bool got threaded down into
+    // eval_plus()/eval_minus()/eval_times().
+    let max = i64::max_value().to_string();
+    assert!(apply_checked_ex("_ + _", &max, true).is_err());
+    assert!(apply_checked_ex("_ + _", &max, false).is_ok());
+}
+
+#[test]
+fn checked_arithmetic_matches_unchecked_within_range() {
+    assert_eq!("4", apply_checked("_ + _", "2", true));
+    assert_eq!("4", apply_checked("_ + _", "2", false));
+}
+
+#[test]
+fn checked_abs_of_i64_min_errors() {
+    // abs(i64::MIN) has no positive representation in i64; unchecked
+    // abs() used to mishandle this (wrap back to i64::MIN itself), while
+    // checked mode should report it as an overflow instead.
+    let min = i64::min_value().to_string();
+    assert!(apply_checked_ex("abs(_)", &min, true).is_err());
+}
+
+#[test]
+fn lines_mode_sees_whole_input_as_array() {
+    // `-l` was stubbed as a TODO in main() with no way to exercise it
+    // from library code until ap::apply_lines_checked() gave it a
+    // distinct evaluation path from the per-line ap::apply_checked().
+    assert_eq!("3", apply_lines("len(_)", "a\nb\nc"));
+}
+
+#[test]
+fn lines_mode_whole_stream_join() {
+    assert_eq!("c\nb\na", apply_lines("join(rev(_), \"\\n\")", "a\nb\nc"));
+}
+
+#[test]
+fn math_functions_reachable() {
+    // abs/sin/gcd/etc. had Rust implementations in api::math but were
+    // never wired up via Context::define_unary/define_binary, so calling
+    // them from an actual expression would have failed with "not defined"
+    // until this request's commit restored the registration mechanism.
+    assert_eq!("0.0", eval("sin(0)"));
+    assert_eq!("6", eval("gcd(12, 18)"));
+    assert_eq!("120", eval("factorial(5)"));
+}
+
+#[test]
+fn math_constants_reachable() {
+    assert_eq!("true", eval("e > 2.7 && e < 2.8"));
+    assert_eq!("true", eval("tau > 6.28 && tau < 6.29"));
+    assert_eq!("true", eval("phi > 1.6 && phi < 1.7"));
+}
+
+#[test]
+fn map_functions_reachable() {
+    // keys/values/items/to_map had working implementations but no
+    // define_unary call, so index_map_value() above was actually failing
+    // at to_map() with "not defined" before this request's
+    // commit, not exercising map indexing at all.
+    // keys()/values()/items() don't guarantee an order, so just check counts.
+    assert_eq!("2", eval("len(keys(to_map(zip(split(a.b, .), split(x.y, .)))))"));
+    assert_eq!("2", eval("len(values(to_map(zip(split(a.b, .), split(x.y, .)))))"));
+    assert_eq!("2", eval("len(items(to_map(zip(split(a.b, .), split(x.y, .)))))"));
+}
+
+#[test]
+fn base64_and_hex_codecs_reachable() {
+    // b64encode/b64decode/hexencode/hexdecode had working implementations
+    // but no define_unary call, so they raised "not defined" until this
+    // request's commit.
+    assert_eq!("aGVsbG8=", eval(r#"b64encode("hello")"#));
+    assert_eq!("hello", eval(r#"b64decode("aGVsbG8=")"#));
+    assert_eq!("68656c6c6f", eval(r#"hexencode("hello")"#));
+    assert_eq!("hello", eval(r#"hexdecode("68656c6c6f")"#));
+}
+
+#[test]
+fn complex_functions_reachable() {
+    // complex/re/im/conj/arg had Rust implementations in api::math but no
+    // define_binary/define_unary call, so this whole family raised
+    // "not defined" until this request's commit. The `i` constant had the
+    // same problem.
+    assert_eq!("3.0", eval("re(complex(3, 4))"));
+    assert_eq!("4.0", eval("im(complex(3, 4))"));
+    assert_eq!("3.0", eval("re(i * i * -3)"));
+}
+
+#[test]
+fn rng_seed_is_deterministic() {
+    // seed()/rand()/randint()/choice()/shuffle()/sample() had Rust
+    // implementations in api::math but no define_*_ctx call, so this
+    // whole family raised "not defined" until this request's commit.
+    // `(seed(n) + 0) ?? 0` runs seed() for its side effect, then falls
+    // back to 0 past the Empty+Integer type error, before the `+
+    // randint(...)` on the right is evaluated.
+    let draw = || eval("((seed(1234) + 0) ?? 0) + randint(0, 1000000)");
+    assert_eq!(draw(), draw());
+}
+
+#[test]
+fn base_combinators_reachable() {
+    // Same story as math_functions_reachable(): base.rs's combinators had
+    // Rust implementations but no define_*/define_*_ctx call, so this
+    // whole family raised "not defined" until this request's commit.
+    assert_eq!("a.b", eval("take(2, split(a.b.c.d, .)) | join(.)"));
+    assert_eq!("c.d", eval("drop(2, split(a.b.c.d, .)) | join(.)"));
+}
+
 
 // Assertions.
 
@@ -227,6 +475,67 @@ fn eval(expr: &str) -> String {
     apply(expr, "unused")
 }
 
+/// Like `apply()`, but goes through the `--checked` overflow-checking path.
+fn apply_checked(expr: &str, input: &str, checked: bool) -> String {
+    match apply_checked_ex(expr, input, checked) {
+        Ok(output) => output,
+        Err(err) => { panic!("apply_checked() error: {}", err); }
+    }
+}
+
+fn apply_checked_ex(expr: &str, input: &str, checked: bool) -> Result<String, io::Error> {
+    let mut extra_newline = false;
+    let mut input = input.to_string();
+    if !input.ends_with("\n") {
+        input.push('\n');
+        extra_newline = true;
+    }
+
+    let mut output: Vec<u8> = Vec::new();
+    try!(ap::apply_checked(expr, checked, input.as_bytes(), &mut output));
+
+    let mut result = try!(
+        from_utf8(&output)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    ).to_string();
+    if extra_newline {
+        result.pop();  // remove trailing \n
+    }
+    Ok(result)
+}
+
+/// Like `apply()`, but goes through the `-l`/lines whole-input path:
+/// the expression sees all of `input` at once, as a single `Value::Array`
+/// of lines, rather than being applied to each line separately.
+fn apply_lines(expr: &str, input: &str) -> String {
+    match apply_lines_ex(expr, input) {
+        Ok(output) => output,
+        Err(err) => { panic!("apply_lines() error: {}", err); }
+    }
+}
+
+fn apply_lines_ex(expr: &str, input: &str) -> Result<String, io::Error> {
+    let mut extra_newline = false;
+    let mut input = input.to_string();
+    if !input.ends_with("\n") {
+        input.push('\n');
+        extra_newline = true;
+    }
+
+    let mut output: Vec<u8> = Vec::new();
+    try!(ap::apply_lines_checked(expr, false, input.as_bytes(), &mut output));
+
+    let mut result = try!(
+        from_utf8(&output)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    ).to_string();
+    if extra_newline {
+        result.pop();  // remove trailing \n
+    }
+    Ok(result)
+}
+
+
 /// Return the string representation of Value::Empty.
 fn empty() -> String {
     format!("{}", ap::Value::Empty)